@@ -0,0 +1,95 @@
+//! 插件被不断开关时的分发吞吐量，对比快照分发与旧的持有 `Bot` 读锁的分发。
+//!
+//! 运行：`cargo bench --features bench --bench dispatch`
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use kovi::bench::{DispatchBench, PRIVATE_MSG};
+use kovi::tokio::runtime::{Builder, Runtime};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const PLUGINS: usize = 16;
+
+/// 另一个线程轮流关闭、开启插件，停止后返回开关的次数
+struct Toggler {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<usize>,
+}
+
+impl Toggler {
+    fn start(bench: Arc<DispatchBench>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let mut toggles = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    let names = bench.plugin_names();
+                    bench.toggle(&names[toggles % names.len()]);
+                    toggles += 1;
+                    std::thread::sleep(Duration::from_micros(50));
+                }
+                toggles
+            }
+        });
+        Toggler { stop, handle }
+    }
+
+    fn stop(self) -> usize {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().expect("toggler thread panicked")
+    }
+}
+
+fn dispatch(c: &mut Criterion) {
+    let bench = Arc::new(DispatchBench::new(PLUGINS));
+    let rt: Runtime = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("build runtime");
+
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Elements(1));
+
+    for toggling in [false, true] {
+        let state = if toggling { "toggling" } else { "idle" };
+        let toggler = toggling.then(|| Toggler::start(bench.clone()));
+
+        group.bench_function(format!("snapshot/{state}"), |b| {
+            b.iter_custom(|iters| {
+                let start = Instant::now();
+                rt.block_on(async {
+                    for _ in 0..iters {
+                        bench.dispatch_snapshot(PRIVATE_MSG).await;
+                    }
+                });
+                start.elapsed()
+            })
+        });
+
+        group.bench_function(format!("locked/{state}"), |b| {
+            b.iter_custom(|iters| {
+                let start = Instant::now();
+                rt.block_on(async {
+                    for _ in 0..iters {
+                        bench.dispatch_locked(PRIVATE_MSG).await;
+                    }
+                });
+                start.elapsed()
+            })
+        });
+
+        if let Some(toggler) = toggler {
+            let toggles = toggler.stop();
+            println!("{toggles} plugin toggles, {} handlers ran", bench.handled());
+        }
+        bench.wait_listening();
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
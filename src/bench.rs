@@ -0,0 +1,137 @@
+//! 分发性能基准使用的内部接口，只在 `bench` feature 下编译，不属于公开 API。
+//!
+//! 运行：`cargo bench --features bench --bench dispatch`
+
+use crate::bot::{Bot, Host, KoviConf, Server, SnapshotCell};
+use crate::event::{InternalEvent, OneBotEvent};
+use crate::plugin::Plugin;
+use crate::types::ApiAndOneshot;
+use crate::{MsgEvent, PluginBuilder, RT, RuntimeBot};
+use parking_lot::RwLock;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// 一条私聊消息
+pub const PRIVATE_MSG: &str = r#"{"time":0,"self_id":1,"post_type":"message","message_type":"private","sub_type":"friend","message_id":1,"user_id":2,"message":[{"type":"text","data":{"text":"hi"}}],"raw_message":"hi","font":0,"sender":{"user_id":2,"nickname":"n"}}"#;
+
+/// 挂载了若干插件的 Bot，每个插件监听一个消息事件
+pub struct DispatchBench {
+    bot: Arc<RwLock<Bot>>,
+    snapshot: Arc<SnapshotCell>,
+    runtime_bot: Arc<RuntimeBot>,
+    api_tx: mpsc::Sender<ApiAndOneshot>,
+    _api_rx: mpsc::Receiver<ApiAndOneshot>,
+    handled: Arc<AtomicUsize>,
+    names: Vec<String>,
+}
+
+impl DispatchBench {
+    pub fn new(plugins: usize) -> Self {
+        let host = Host::IpAddr([127, 0, 0, 1].into());
+        let conf = KoviConf::new(
+            1,
+            None,
+            Server::new(host.clone(), 8081, String::new(), false),
+            false,
+        );
+        let mut bot = Bot::build(&conf);
+        let handled = Arc::new(AtomicUsize::new(0));
+        let names: Vec<String> = (0..plugins).map(|i| format!("p{i}")).collect();
+        for name in &names {
+            let handled = handled.clone();
+            bot.mount_plugin(Plugin::new(
+                name.clone(),
+                "0.0.1".to_string(),
+                Arc::new(move || {
+                    let handled = handled.clone();
+                    Box::pin(async move {
+                        PluginBuilder::on_msg(move |_: Arc<MsgEvent>| {
+                            let handled = handled.clone();
+                            async move {
+                                handled.fetch_add(1, Ordering::Relaxed);
+                            }
+                        });
+                    })
+                }),
+            ));
+        }
+
+        let bot = Arc::new(RwLock::new(bot));
+        let (api_tx, api_rx) = mpsc::channel(1024);
+        let _guard = RT.enter();
+        for name in &names {
+            let builder = PluginBuilder::new(
+                name.clone(),
+                bot.clone(),
+                host.clone(),
+                8081,
+                api_tx.clone(),
+            );
+            bot.read().plugins[name].run(builder);
+        }
+        let runtime_bot =
+            PluginBuilder::new(String::new(), bot.clone(), host, 8081, api_tx.clone()).runtime_bot;
+
+        let snapshot = bot.read().snapshot.clone();
+        let bench = DispatchBench {
+            bot,
+            snapshot,
+            runtime_bot,
+            api_tx,
+            _api_rx: api_rx,
+            handled,
+            names,
+        };
+        bench.wait_listening();
+        bench
+    }
+
+    /// 等待所有开启的插件注册完监听
+    pub fn wait_listening(&self) {
+        while self
+            .snapshot
+            .load()
+            .plugins
+            .iter()
+            .any(|(_, plugin)| *plugin.enabled.borrow() && plugin.listen.list.is_empty())
+        {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    pub fn plugin_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// 通过 `RuntimeBot` 关闭再开启插件，插件重新开启时会再次注册监听
+    pub fn toggle(&self, name: &str) {
+        let _guard = RT.enter();
+        if let Err(e) = self.runtime_bot.disable_plugin(name) {
+            log::error!("Failed to disable plugin '{name}': {e}");
+        }
+        if let Err(e) = self.runtime_bot.enable_plugin(name) {
+            log::error!("Failed to enable plugin '{name}': {e}");
+        }
+    }
+
+    /// 已经运行的监听处理次数
+    pub fn handled(&self) -> usize {
+        self.handled.load(Ordering::Relaxed)
+    }
+
+    /// 从快照分发，不持有 `Bot` 的锁
+    pub async fn dispatch_snapshot(&self, raw: &str) {
+        let msg = InternalEvent::OneBotEvent(OneBotEvent::new(raw));
+        Bot::handler_internal_event(self.snapshot.load(), msg, self.api_tx.clone());
+    }
+
+    /// 旧的分发方式：分发期间一直持有 `Bot` 的读锁
+    pub async fn dispatch_locked(&self, raw: &str) {
+        let bot = self.bot.read();
+        let msg = InternalEvent::OneBotEvent(OneBotEvent::new(raw));
+        Bot::handler_internal_event(bot.snapshot.load(), msg, self.api_tx.clone());
+        drop(bot);
+    }
+}
@@ -1,10 +1,10 @@
 use ahash::{HashMapExt as _, RandomState};
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{Input, Select};
-use plugin_builder::Listen;
 use rand::Rng as _;
+use runtimebot::{MsgQueue, SPLIT_RESPONSE_TIMEOUT};
 #[cfg(feature = "plugin-access-control")]
-use runtimebot::kovi_api::AccessList;
+use runtimebot::kovi_api::{AccessList, AccessRule};
 use serde::{Deserialize, Serialize};
 use serde_json::{self, Value};
 use std::collections::{HashMap, HashSet};
@@ -12,17 +12,23 @@ use std::env;
 use std::fmt::{Debug, Display};
 use std::io::Write as _;
 use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{fs, net::IpAddr, sync::Arc};
 use tokio::sync::mpsc::{self};
 use tokio::sync::watch;
 
+use crate::bot::handler::InternalInternalEvent;
 use crate::error::{BotBuildError, BotError};
 
 use crate::RT;
 #[cfg(feature = "plugin-access-control")]
 pub use crate::bot::runtimebot::kovi_api::AccessControlMode;
 
-use crate::plugin::{Plugin, PluginStatus};
+use crate::bot::message::{MediaConfig, SplitConfig};
+use crate::bot::permission::Roles;
+#[cfg(feature = "save_plugin_status")]
+use crate::plugin::PluginStatus;
+use crate::plugin::{Plugin, PluginDisabledIn, PluginStatusFile};
 use crate::types::KoviAsyncFn;
 
 pub(crate) mod connect;
@@ -32,15 +38,29 @@ pub(crate) mod run;
 // 兼容
 pub use crate::plugin::plugin_builder;
 pub mod event;
+pub mod filter;
 pub mod message;
+pub mod permission;
 pub mod runtimebot;
 
 /// bot结构体
 #[derive(Clone)]
 pub struct Bot {
     pub information: BotInformation,
+    /// 发送拆分消息的队列
+    pub(crate) msg_queue: MsgQueue,
     pub(crate) plugins: HashMap<String, Plugin, RandomState>,
     pub(crate) run_abort: Vec<tokio::task::AbortHandle>,
+    pub(crate) snapshot: Arc<SnapshotCell>,
+    /// 插件构建时修改了插件表，快照还没有发布
+    pub(crate) snapshot_dirty: Arc<AtomicBool>,
+    /// 事件通道与 api 通道的容量
+    pub(crate) channel_capacity: (usize, usize),
+    /// 运行后才有，用于插件出错时通知事件循环
+    pub(crate) event_tx: Option<mpsc::WeakSender<InternalInternalEvent>>,
+    /// 全局黑名单，在所有插件之前生效
+    #[cfg(feature = "plugin-access-control")]
+    pub(crate) blacklist: Vec<AccessRule>,
 }
 impl Drop for Bot {
     fn drop(&mut self) {
@@ -73,14 +93,43 @@ impl Bot {
         C: AsRef<KoviConf>,
     {
         let conf = conf.as_ref();
+        let information = BotInformation {
+            main_admin: conf.config.main_admin,
+            deputy_admins: conf.config.admins.iter().cloned().collect(),
+            server: conf.server.clone(),
+            roles: Roles::default(),
+            media: conf.config.media.clone(),
+            split: conf.config.split.clone(),
+            nicknames: conf
+                .config
+                .nicknames
+                .iter()
+                .filter(|name| !name.is_empty())
+                .cloned()
+                .collect(),
+        };
+        let msg_queue = MsgQueue::new(conf.config.split.clone(), SPLIT_RESPONSE_TIMEOUT);
         Bot {
-            information: BotInformation {
-                main_admin: conf.config.main_admin,
-                deputy_admins: conf.config.admins.iter().cloned().collect(),
-                server: conf.server.clone(),
-            },
+            snapshot: Arc::new(SnapshotCell::new(BotSnapshot {
+                information: information.clone(),
+                msg_queue: msg_queue.clone(),
+                plugins: Vec::new(),
+                event_tx: None,
+                #[cfg(feature = "plugin-access-control")]
+                blacklist: Vec::new(),
+            })),
+            snapshot_dirty: Arc::default(),
+            information,
+            msg_queue,
             plugins: HashMap::<_, _, RandomState>::new(),
             run_abort: Vec::new(),
+            channel_capacity: (
+                conf.config.event_channel_capacity.max(1),
+                conf.config.api_channel_capacity.max(1),
+            ),
+            event_tx: None,
+            #[cfg(feature = "plugin-access-control")]
+            blacklist: Vec::new(),
         }
     }
 
@@ -99,7 +148,13 @@ impl Bot {
             name: name.clone(),
             version,
             main,
-            listen: Listen::default(),
+            listen: Arc::default(),
+            limiter: None,
+            sequencer: None,
+            health: Arc::default(),
+            timeout: None,
+            disabled_in: PluginDisabledIn::default(),
+            meta: Arc::default(),
 
             #[cfg(feature = "plugin-access-control")]
             access_control: false,
@@ -109,11 +164,47 @@ impl Bot {
             access_list: AccessList::default(),
         };
         self.plugins.insert(name, bot_plugin);
+        self.publish_snapshot();
     }
 
     /// 挂载插件。
     pub fn mount_plugin(&mut self, plugin: Plugin) {
         self.plugins.insert(plugin.name.clone(), plugin);
+        self.publish_snapshot();
+    }
+
+    /// 用当前的插件表与 `BotInformation` 生成新的分发快照并替换旧的。
+    ///
+    /// 所有修改了插件表或 `BotInformation` 的地方，都需要在修改后调用此函数。
+    pub(crate) fn publish_snapshot(&self) {
+        self.snapshot_dirty.store(false, Ordering::Release);
+        let plugins = self
+            .plugins
+            .iter()
+            .map(|(name, plugin)| (Arc::new(name.clone()), plugin.clone()))
+            .collect();
+
+        self.snapshot.store(BotSnapshot {
+            information: self.information.clone(),
+            msg_queue: self.msg_queue.clone(),
+            plugins,
+            event_tx: self.event_tx.clone(),
+            #[cfg(feature = "plugin-access-control")]
+            blacklist: self.blacklist.clone(),
+        });
+    }
+
+    /// 插件构建时的修改只标记快照过期，在插件的 `main` 让出或结束时一起发布，
+    /// 避免每注册一个监听都生成一次快照
+    pub(crate) fn mark_snapshot_dirty(&self) {
+        self.snapshot_dirty.store(true, Ordering::Release);
+    }
+
+    /// 快照过期时重新发布
+    pub(crate) fn publish_snapshot_if_dirty(&self) {
+        if self.snapshot_dirty.swap(false, Ordering::AcqRel) {
+            self.publish_snapshot();
+        }
     }
 
     /// 读取本地Kovi.conf.toml文件
@@ -161,6 +252,16 @@ impl Bot {
     ///
     /// 如果配置文件读取失败或者解析toml失败，将会保留插件默认状态
     pub fn set_plugin_startup_use_file(mut self) -> Self {
+        self.set_plugin_startup_use_file_ref();
+        self
+    }
+
+    /// 使用KoviConf设置插件在Bot启动时的状态
+    ///
+    /// 如果配置文件中没有对应的插件，将会被忽略，保留插件默认状态
+    ///
+    /// 如果配置文件读取失败或者解析toml失败，将会保留插件默认状态
+    pub fn set_plugin_startup_use_file_ref(&mut self) {
         let file_path = "kovi.plugin.toml";
         let content = match fs::read_to_string(file_path) {
             Ok(v) => {
@@ -169,23 +270,28 @@ impl Bot {
             }
             Err(e) => {
                 log::debug!("Failed to read file: {}", e);
-                return self;
+                return;
             }
         };
-        let mut plugin_status_map: HashMap<String, PluginStatus> = match toml::from_str(&content) {
+        let mut status_file = match PluginStatusFile::from_toml(&content) {
             Ok(v) => v,
             Err(e) => {
                 log::debug!("Failed to parse toml: {}", e);
-                return self;
+                return;
             }
         };
 
+        #[cfg(feature = "plugin-access-control")]
+        {
+            self.blacklist = status_file.global_blacklist;
+        }
         for (name, plugin) in self.plugins.iter_mut() {
-            if let Some(plugin_status) = plugin_status_map.remove(name) {
+            if let Some(plugin_status) = status_file.plugins.remove(name) {
                 plugin.enable_on_startup = plugin_status.enable_on_startup;
                 plugin.enabled.send_modify(|v| {
                     *v = plugin_status.enable_on_startup;
                 });
+                plugin.disabled_in = plugin_status.disabled_in;
                 #[cfg(feature = "plugin-access-control")]
                 {
                     plugin.access_control = plugin_status.access_control;
@@ -194,49 +300,40 @@ impl Bot {
                 }
             }
         }
+        self.publish_snapshot();
+    }
 
+    /// 从 `kovi.roles.toml` 读取Bot的自定义身份
+    ///
+    /// 如果文件读取失败或者解析toml失败，将会保留当前的自定义身份
+    pub fn set_roles_use_file(mut self) -> Self {
+        self.set_roles_use_file_ref();
         self
     }
 
-    /// 使用KoviConf设置插件在Bot启动时的状态
+    /// 从 `kovi.roles.toml` 读取Bot的自定义身份
     ///
-    /// 如果配置文件中没有对应的插件，将会被忽略，保留插件默认状态
-    ///
-    /// 如果配置文件读取失败或者解析toml失败，将会保留插件默认状态
-    pub fn set_plugin_startup_use_file_ref(&mut self) {
-        let file_path = "kovi.plugin.toml";
+    /// 如果文件读取失败或者解析toml失败，将会保留当前的自定义身份
+    pub fn set_roles_use_file_ref(&mut self) {
+        let file_path = "kovi.roles.toml";
         let content = match fs::read_to_string(file_path) {
-            Ok(v) => {
-                log::debug!("Set plugin startup use file successfully");
-                v
-            }
+            Ok(v) => v,
             Err(e) => {
                 log::debug!("Failed to read file: {}", e);
                 return;
             }
         };
-        let mut plugin_status_map: HashMap<String, PluginStatus> = match toml::from_str(&content) {
-            Ok(v) => v,
+        match toml::from_str(&content) {
+            Ok(roles) => {
+                log::debug!("Set roles use file successfully");
+                self.information.roles = roles;
+            }
             Err(e) => {
                 log::debug!("Failed to parse toml: {}", e);
                 return;
             }
         };
-
-        for (name, plugin) in self.plugins.iter_mut() {
-            if let Some(plugin_status) = plugin_status_map.remove(name) {
-                plugin.enable_on_startup = plugin_status.enable_on_startup;
-                plugin.enabled.send_modify(|v| {
-                    *v = plugin_status.enable_on_startup;
-                });
-                #[cfg(feature = "plugin-access-control")]
-                {
-                    plugin.access_control = plugin_status.access_control;
-                    plugin.list_mode = plugin_status.list_mode;
-                    plugin.access_list = plugin_status.access_list;
-                }
-            }
-        }
+        self.publish_snapshot();
     }
 
     /// 设置全部插件在Bot启动时的状态
@@ -302,26 +399,47 @@ impl Bot {
         }
     }
 
-    #[cfg(any(feature = "save_plugin_status", feature = "save_bot_admin"))]
+    #[cfg(any(
+        feature = "save_plugin_status",
+        feature = "save_bot_admin",
+        feature = "save_bot_roles"
+    ))]
     pub(crate) fn save_bot_status(&self) {
         #[cfg(feature = "save_plugin_status")]
         {
             let _file_path = "kovi.plugin.toml";
 
-            let mut plugin_status = HashMap::new();
+            // 过期的规则不再保存
+            #[cfg(feature = "plugin-access-control")]
+            let now = chrono::Local::now().timestamp();
+            let mut status_file = PluginStatusFile::default();
+            #[cfg(feature = "plugin-access-control")]
+            {
+                status_file.global_blacklist = self
+                    .blacklist
+                    .iter()
+                    .filter(|rule| !rule.is_expired(now))
+                    .cloned()
+                    .collect();
+            }
             for (name, plugin) in self.plugins.iter() {
-                plugin_status.insert(name.clone(), PluginStatus {
+                status_file.plugins.insert(name.clone(), PluginStatus {
                     enable_on_startup: *plugin.enabled.borrow(),
+                    disabled_in: plugin.disabled_in.clone(),
                     #[cfg(feature = "plugin-access-control")]
                     access_control: plugin.access_control,
                     #[cfg(feature = "plugin-access-control")]
                     list_mode: plugin.list_mode,
                     #[cfg(feature = "plugin-access-control")]
-                    access_list: plugin.access_list.clone(),
+                    access_list: {
+                        let mut access_list = plugin.access_list.clone();
+                        access_list.rules.retain(|rule| !rule.is_expired(now));
+                        access_list
+                    },
                 });
             }
 
-            let serialized = match toml::to_string(&plugin_status) {
+            let serialized = match toml::to_string(&status_file) {
                 Ok(s) => s,
                 Err(e) => {
                     log::error!("Failed to serialize plugin status: {}", e);
@@ -369,6 +487,22 @@ impl Bot {
                 }
             }
         }
+
+        #[cfg(feature = "save_bot_roles")]
+        {
+            let file_path = "kovi.roles.toml";
+
+            let serialized = match toml::to_string(&self.information.roles) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to serialize roles: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = fs::write(file_path, serialized) {
+                log::error!("Failed to write roles to file: {}", e);
+            }
+        }
     }
 }
 
@@ -405,6 +539,25 @@ pub struct Config {
     pub main_admin: i64,
     pub admins: Vec<i64>,
     pub debug: bool,
+    /// 事件通道的容量
+    #[serde(default = "default_channel_capacity")]
+    pub event_channel_capacity: usize,
+    /// api 通道的容量
+    #[serde(default = "default_channel_capacity")]
+    pub api_channel_capacity: usize,
+    /// 发送本地文件时的编码方式
+    #[serde(default)]
+    pub media: MediaConfig,
+    /// 过长消息的拆分
+    #[serde(default)]
+    pub split: SplitConfig,
+    /// Bot 的称呼，群消息以此开头时视为对 Bot 说的，见 `MsgEvent::to_me`
+    #[serde(default)]
+    pub nicknames: Vec<String>,
+}
+
+fn default_channel_capacity() -> usize {
+    32
 }
 
 impl KoviConf {
@@ -414,18 +567,77 @@ impl KoviConf {
                 main_admin,
                 admins: admins.unwrap_or_default(),
                 debug,
+                event_channel_capacity: default_channel_capacity(),
+                api_channel_capacity: default_channel_capacity(),
+                media: MediaConfig::default(),
+                split: SplitConfig::default(),
+                nicknames: Vec::new(),
             },
             server,
         }
     }
 }
 
+/// 事件分发时使用的只读快照
+///
+/// 插件表与 `BotInformation` 的修改都在 `Bot` 的写锁内完成，完成后整体替换快照。
+/// 分发事件只需要取出当前快照，不会被写操作阻塞，也不会阻塞写操作。
+pub(crate) struct BotSnapshot {
+    pub(crate) information: BotInformation,
+    pub(crate) msg_queue: MsgQueue,
+    pub(crate) plugins: Vec<(Arc<String>, Plugin)>,
+    pub(crate) event_tx: Option<mpsc::WeakSender<InternalInternalEvent>>,
+    #[cfg(feature = "plugin-access-control")]
+    pub(crate) blacklist: Vec<AccessRule>,
+}
+
+/// 存放当前快照，读写都只在替换 `Arc` 的一瞬间持有锁
+pub(crate) struct SnapshotCell {
+    current: parking_lot::RwLock<Arc<BotSnapshot>>,
+}
+
+impl SnapshotCell {
+    pub(crate) fn new(snapshot: BotSnapshot) -> Self {
+        SnapshotCell {
+            current: parking_lot::RwLock::new(Arc::new(snapshot)),
+        }
+    }
+
+    /// 取出当前快照
+    pub(crate) fn load(&self) -> Arc<BotSnapshot> {
+        self.current.read().clone()
+    }
+
+    /// 替换为新的快照
+    pub(crate) fn store(&self, snapshot: BotSnapshot) {
+        let snapshot = Arc::new(snapshot);
+        let old = std::mem::replace(&mut *self.current.write(), snapshot);
+        // 旧快照在锁外释放
+        drop(old);
+    }
+}
+
 /// bot信息结构体
 #[derive(Debug, Clone)]
 pub struct BotInformation {
     pub main_admin: i64,
     pub deputy_admins: HashSet<i64>,
     pub server: Server,
+    /// 自定义身份，见 `Permission::Role`
+    pub roles: Roles,
+    /// 发送本地文件时的编码方式
+    pub media: MediaConfig,
+    /// Bot 的称呼
+    pub nicknames: Vec<String>,
+    /// 过长消息的拆分配置
+    pub split: SplitConfig,
+}
+
+impl BotInformation {
+    /// 是否为 Bot 的主管理员或副管理员
+    pub fn is_admin(&self, user_id: i64) -> bool {
+        self.main_admin == user_id || self.deputy_admins.contains(&user_id)
+    }
 }
 /// server信息
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -663,6 +875,7 @@ macro_rules! build_bot {
             )*
 
             bot.set_plugin_startup_use_file_ref();
+            bot.set_roles_use_file_ref();
             bot
         }
     };
@@ -674,7 +887,7 @@ fn build_bot() {
         123456,
         None,
         Server {
-            host: Host::IpAddr("127.0.0.1".parse().unwrap()),
+            host: Host::IpAddr("127.0.0.1".parse().expect("valid ip address")),
             port: 8081,
             access_token: "".to_string(),
             secure: false,
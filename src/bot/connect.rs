@@ -1,7 +1,7 @@
 use super::Server;
 use super::{ApiReturn, Bot, Host};
 use crate::bot::handler::InternalInternalEvent;
-use crate::event::InternalEvent;
+use crate::event::{InternalEvent, OneBotEvent};
 use crate::types::ApiAndOneshot;
 use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
@@ -211,7 +211,7 @@ async fn ws_event_connect_read(
         let text = msg.to_text().expect("unreachable");
         if let Err(e) = event_tx
             .send(InternalInternalEvent::OneBotEvent(
-                InternalEvent::OneBotEvent(text.to_string()),
+                InternalEvent::OneBotEvent(OneBotEvent::new(text)),
             ))
             .await
         {
@@ -282,10 +282,10 @@ async fn ws_send_api_read(
             Err(return_value)
         };
 
-        if let Some(tx) = api_tx_cache.1 {
-            if tx.send(return_value.clone()).is_err() {
-                log::debug!("Return Api to plugin failed, the receiver has been closed")
-            }
+        if let Some(tx) = api_tx_cache.1
+            && tx.send(return_value.clone()).is_err()
+        {
+            log::debug!("Return Api to plugin failed, the receiver has been closed")
         };
 
         event_tx
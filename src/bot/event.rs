@@ -1,9 +1,11 @@
 use crate::{
-    bot::BotInformation,
+    bot::{BotInformation, message::Message, runtimebot::MsgQueue},
     types::{ApiAndOneshot, ApiAndRuturn},
 };
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::any::Any;
+use std::sync::{Arc, OnceLock};
 
 pub use admin_msg_event::AdminMsgEvent;
 pub use group_msg_event::GroupMsgEvent;
@@ -11,6 +13,7 @@ pub use msg_event::MsgEvent;
 pub use msg_send_from_kovi_event::MsgSendFromKoviEvent;
 pub use msg_send_from_server_event::MsgSendFromServerEvent;
 pub use notice_event::NoticeEvent;
+pub use plugin_error_event::{PluginErrorEvent, PluginErrorKind};
 pub use private_msg_event::PrivateMsgEvent;
 pub use request_event::RequestEvent;
 
@@ -21,6 +24,7 @@ pub mod msg_event;
 pub mod msg_send_from_kovi_event;
 pub mod msg_send_from_server_event;
 pub mod notice_event;
+pub mod plugin_error_event;
 pub mod private_msg_event;
 pub mod request_event;
 
@@ -31,12 +35,22 @@ pub type AllNoticeEvent = NoticeEvent;
 #[deprecated(since = "0.11.0", note = "请使用 `RequestEvent` 代替")]
 pub type AllRequestEvent = RequestEvent;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Sex {
     Male,
     Female,
 }
 
+/// 群成员身份，按权限从低到高排列，可以直接比较大小
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Member,
+    Admin,
+    Owner,
+}
+
 #[derive(Debug, Clone)]
 pub struct Sender {
     pub user_id: i64,
@@ -46,9 +60,55 @@ pub struct Sender {
     pub age: Option<i32>,
     pub area: Option<String>,
     pub level: Option<String>,
-    pub role: Option<String>,
+    /// 群成员身份，私聊时为 `None`
+    pub role: Option<Role>,
     pub title: Option<String>,
 }
+
+impl Sender {
+    /// 是否为群主
+    pub fn is_group_owner(&self) -> bool {
+        self.role == Some(Role::Owner)
+    }
+
+    /// 是否为群管理员，群主也算
+    pub fn is_group_admin(&self) -> bool {
+        self.role >= Some(Role::Admin)
+    }
+}
+
+/// 消息事件的发送人，不是消息事件时返回 `None`
+pub(crate) fn message_sender(event: &dyn Event) -> Option<&Sender> {
+    let event = event as &dyn Any;
+    if let Some(event) = event.downcast_ref::<MsgEvent>() {
+        Some(&event.sender)
+    } else if let Some(event) = event.downcast_ref::<GroupMsgEvent>() {
+        Some(&event.sender)
+    } else if let Some(event) = event.downcast_ref::<PrivateMsgEvent>() {
+        Some(&event.sender)
+    } else if let Some(event) = event.downcast_ref::<AdminMsgEvent>() {
+        Some(&event.sender)
+    } else {
+        None
+    }
+}
+
+/// 消息事件的消息与 Bot 的 QQ 号，不是消息事件时返回 `None`
+pub(crate) fn event_message(event: &dyn Event) -> Option<(&Message, i64)> {
+    let event = event as &dyn Any;
+    if let Some(event) = event.downcast_ref::<MsgEvent>() {
+        Some((&event.message, event.self_id))
+    } else if let Some(event) = event.downcast_ref::<GroupMsgEvent>() {
+        Some((&event.message, event.self_id))
+    } else if let Some(event) = event.downcast_ref::<PrivateMsgEvent>() {
+        Some((&event.message, event.self_id))
+    } else if let Some(event) = event.downcast_ref::<AdminMsgEvent>() {
+        Some((&event.message, event.self_id))
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Anonymous {
     pub id: i64,
@@ -102,6 +162,9 @@ pub trait Event: Any + Send + Sync {
     ///
     /// 在一个消息周期内，Kovi 运行时会缓存此事件。
     ///
+    /// OneBot 事件的 json 在一个消息周期内只会被解析一次，请通过 `event.json()` 获取共享的解析结果，
+    /// 而不是自己再 `serde_json::from_str` 一遍。
+    ///
     /// 不需要的信息用 `_` 忽略，例如：
     ///
     /// ```
@@ -114,10 +177,8 @@ pub trait Event: Any + Send + Sync {
     ///     where
     ///         Self: Sized,
     ///     {
-    ///         let InternalEvent::OneBotEvent(json_str) = event else {
-    ///             return None;
-    ///         };
-    ///         let event: LifecycleEvent = serde_json::from_str(json_str).ok()?;
+    ///         let json = event.json()?;
+    ///         let event = LifecycleEvent::deserialize(json.as_ref()).ok()?;
     ///         if event.meta_event_type == "lifecycle" {
     ///             Some(event)
     ///         } else {
@@ -147,34 +208,270 @@ pub trait Event: Any + Send + Sync {
     ) -> Option<Self>
     where
         Self: Sized;
+
+    /// 事件所在的群，不在群内发生的事件返回 `None`
+    ///
+    /// 插件黑白名单、顺序处理等按群区分的功能会使用它，自定义事件可按需实现。
+    fn event_group_id(&self) -> Option<i64> {
+        None
+    }
+
+    /// 触发事件的用户，没有触发者的事件返回 `None`
+    fn event_user_id(&self) -> Option<i64> {
+        None
+    }
 }
 
 /// 事件
 pub enum InternalEvent {
     /// 来自OneBot的事件
-    OneBotEvent(String),
+    OneBotEvent(OneBotEvent),
     /// 来自Kovi发送给服务端并包含了返回结果
     OneBotApiEvent(ApiAndRuturn),
 }
 
+impl InternalEvent {
+    /// 获取 OneBot 事件共享的 json 解析结果，如果不是 OneBot 事件或者解析失败，返回 `None`
+    pub fn json(&self) -> Option<&Arc<Value>> {
+        match self {
+            InternalEvent::OneBotEvent(event) => event.json(),
+            InternalEvent::OneBotApiEvent(_) => None,
+        }
+    }
+
+    /// 收到此事件的 Bot 的发送队列
+    pub(crate) fn msg_queue(&self) -> MsgQueue {
+        match self {
+            InternalEvent::OneBotEvent(event) => event.msg_queue.clone(),
+            InternalEvent::OneBotApiEvent(_) => MsgQueue::default(),
+        }
+    }
+}
+
+/// 来自 OneBot 的原始事件
+///
+/// 包含原始字符串与惰性解析的 json，在一个消息周期内，所有 `Event` 共享同一份解析结果。
+///
+/// 实现了 `Deref<Target = str>`，旧的 `serde_json::from_str(json_str)` 写法仍然可以使用。
+pub struct OneBotEvent {
+    raw: String,
+    json: OnceLock<Option<Arc<Value>>>,
+    /// 由 Bot 在分发前设置，消息事件通过它发送拆分后的消息
+    pub(crate) msg_queue: MsgQueue,
+}
+
+impl OneBotEvent {
+    pub fn new<S: Into<String>>(raw: S) -> Self {
+        OneBotEvent {
+            raw: raw.into(),
+            json: OnceLock::new(),
+            msg_queue: MsgQueue::default(),
+        }
+    }
+
+    /// 原始的 json 字符串
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// 解析后的 json，只会在第一次调用时解析，解析失败返回 `None`
+    pub fn json(&self) -> Option<&Arc<Value>> {
+        self.json
+            .get_or_init(|| serde_json::from_str(&self.raw).ok().map(Arc::new))
+            .as_ref()
+    }
+}
+
+impl std::ops::Deref for OneBotEvent {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.raw
+    }
+}
+
+impl AsRef<str> for OneBotEvent {
+    fn as_ref(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl std::fmt::Display for OneBotEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl std::fmt::Debug for OneBotEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("OneBotEvent").field(&self.raw).finish()
+    }
+}
+
+#[test]
+fn onebot_event_parse_once() {
+    let event = InternalEvent::OneBotEvent(OneBotEvent::new(
+        r#"{"time":1,"self_id":2,"post_type":"notice","notice_type":"group_recall"}"#,
+    ));
+
+    let first = event.json().expect("event json").clone();
+    let second = event.json().expect("event json").clone();
+    assert!(Arc::ptr_eq(&first, &second));
+
+    // 旧的写法依旧可以解析
+    let InternalEvent::OneBotEvent(json_str) = &event else {
+        unreachable!()
+    };
+    let value: Value = serde_json::from_str(json_str).expect("valid json");
+    assert_eq!(&value, first.as_ref());
+
+    // 事件共享解析结果，不再复制
+    let notice = NoticeEvent::new(first.clone()).expect("valid notice event");
+    assert!(Arc::ptr_eq(&notice.original_json, &first));
+}
+
 #[test]
 fn post_type_is_ok() {
     use serde_json::json;
 
     assert_eq!(
         PostType::Message,
-        serde_json::from_value::<PostType>(json!("message")).unwrap()
+        serde_json::from_value::<PostType>(json!("message")).expect("valid json")
     );
     assert_eq!(
         PostType::Notice,
-        serde_json::from_value::<PostType>(json!("notice")).unwrap()
+        serde_json::from_value::<PostType>(json!("notice")).expect("valid json")
     );
     assert_eq!(
         PostType::Request,
-        serde_json::from_value::<PostType>(json!("request")).unwrap()
+        serde_json::from_value::<PostType>(json!("request")).expect("valid json")
     );
     assert_eq!(
         PostType::MetaEvent,
-        serde_json::from_value::<PostType>(json!("meta_event")).unwrap()
+        serde_json::from_value::<PostType>(json!("meta_event")).expect("valid json")
+    );
+}
+
+#[test]
+fn sender_role_is_typed() {
+    use crate::bot::{Host, Server};
+    use serde_json::json;
+
+    let bot_info = BotInformation {
+        main_admin: 2,
+        deputy_admins: Default::default(),
+        server: Server::new(
+            Host::Domain("localhost".to_string()),
+            8081,
+            String::new(),
+            false,
+        ),
+        roles: Default::default(),
+        media: Default::default(),
+        split: Default::default(),
+        nicknames: vec!["kovi".to_string()],
+    };
+    let json = json!({
+        "time": 0, "self_id": 1, "post_type": "message", "message_type": "group",
+        "sub_type": "normal", "message_id": 1, "group_id": 3, "user_id": 2,
+        "message": [{"type": "text", "data": {"text": "hi"}}], "raw_message": "hi", "font": 0,
+        "sender": {"user_id": 2, "sex": "female", "role": "admin"}
+    });
+    let (api_tx, _api_rx) = tokio::sync::mpsc::channel(1);
+    let event = MsgEvent::new(api_tx, Arc::new(json), &bot_info, Default::default())
+        .expect("valid message event");
+
+    assert_eq!(event.sender.sex, Some(Sex::Female));
+    assert_eq!(event.sender.role, Some(Role::Admin));
+    assert!(event.sender_is_group_admin());
+    assert!(!event.sender_is_group_owner());
+    assert!(event.sender_is_bot_admin());
+    assert!(Role::Owner > Role::Admin && Role::Admin > Role::Member);
+}
+
+#[test]
+fn to_me_from_mentions_and_nicknames() {
+    use crate::bot::{Host, Server};
+    use serde_json::json;
+
+    let bot_info = BotInformation {
+        main_admin: 2,
+        deputy_admins: Default::default(),
+        server: Server::new(
+            Host::Domain("localhost".to_string()),
+            8081,
+            String::new(),
+            false,
+        ),
+        roles: Default::default(),
+        media: Default::default(),
+        split: Default::default(),
+        nicknames: vec!["kovi".to_string(), "小助手".to_string()],
+    };
+    let event = |group_id: Option<i64>, message: serde_json::Value| {
+        let json = json!({
+            "time": 0, "self_id": 1, "post_type": "message", "message_type": "group",
+            "sub_type": "normal", "message_id": 1, "group_id": group_id, "user_id": 2,
+            "message": message, "raw_message": "", "font": 0,
+            "sender": {"user_id": 2}
+        });
+        let (api_tx, _api_rx) = tokio::sync::mpsc::channel(1);
+        MsgEvent::new(api_tx, Arc::new(json), &bot_info, Default::default())
+            .expect("valid message event")
+    };
+
+    let at_me = event(
+        Some(3),
+        json!([
+            {"type": "reply", "data": {"id": "9"}},
+            {"type": "at", "data": {"qq": "1"}},
+            {"type": "text", "data": {"text": " help "}},
+            {"type": "at", "data": {"qq": "4"}},
+            {"type": "at", "data": {"qq": "all"}},
+            {"type": "at", "data": {"qq": "4"}},
+        ]),
+    );
+    assert!(at_me.to_me && at_me.is_at_me() && at_me.is_at_all());
+    assert_eq!(at_me.mentioned_users(), vec![1, 4]);
+    assert_eq!(at_me.text_without_mentions(), "help");
+
+    let by_name = event(
+        Some(3),
+        json!([{"type": "text", "data": {"text": "kovi help"}}]),
+    );
+    assert!(by_name.to_me && !by_name.is_at_me());
+    let other = event(
+        Some(3),
+        json!([{"type": "text", "data": {"text": "help kovi"}}]),
+    );
+    assert!(!other.to_me);
+    for (text, to_me) in [
+        ("kovi", true),
+        ("kovi，help", true),
+        ("kovi\nhelp", true),
+        ("kovihelp", false),
+        ("kovi2 help", false),
+        ("  kovi help", true),
+        ("小助手帮我看看", true),
+        ("小助手", true),
+        ("小助", false),
+        ("帮我小助手", false),
+    ] {
+        let by_name = event(Some(3), json!([{"type": "text", "data": {"text": text}}]));
+        assert_eq!(by_name.to_me, to_me, "{text}");
+    }
+    let split_text = event(
+        Some(3),
+        json!([
+            {"type": "at", "data": {"qq": "4"}},
+            {"type": "text", "data": {"text": " 小"}},
+            {"type": "face", "data": {"id": "1"}},
+            {"type": "text", "data": {"text": "助手帮我"}},
+        ]),
     );
+    assert!(split_text.to_me);
+    let at_number = event(Some(3), json!([{"type": "at", "data": {"qq": 1}}]));
+    assert!(at_number.to_me);
+    let private = event(None, json!([{"type": "text", "data": {"text": "help"}}]));
+    assert!(private.to_me);
 }
@@ -3,7 +3,7 @@ use crate::MsgEvent;
 use crate::bot::BotInformation;
 use crate::bot::event::InternalEvent;
 use crate::bot::plugin_builder::event::{Event, PostType};
-use crate::bot::runtimebot::{CanSendApi, send_api_request_with_forget};
+use crate::bot::runtimebot::{CanSendApi, MsgQueue, MsgTarget};
 use crate::error::EventBuildError;
 use crate::types::ApiAndOneshot;
 use crate::{Message, bot::SendApi};
@@ -11,6 +11,7 @@ use log::info;
 use serde::Serialize;
 use serde_json::value::Index;
 use serde_json::{self, Value, json};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 #[cfg(feature = "cqstring")]
@@ -49,11 +50,16 @@ pub struct AdminMsgEvent {
     pub text: Option<String>,
     /// 处理过的文本，会解析成人类易读形式，里面会包含\[image\]\[face\]等解析后字符串
     pub human_text: String,
-    /// 原始的onebot消息，已处理成json格式
-    pub original_json: Value,
+    /// 原始的onebot消息，已处理成json格式，同一消息周期内的事件共享同一份，
+    /// 需要 `Value` 时使用 `Value::clone(&event.original_json)`
+    pub original_json: Arc<Value>,
+    /// 是否是对 Bot 说的，见 `MsgEvent::to_me`
+    pub to_me: bool,
 
     /// 不推荐的消息发送方式
     pub api_tx: mpsc::Sender<ApiAndOneshot>,
+
+    pub(crate) msg_queue: MsgQueue,
 }
 
 impl Event for AdminMsgEvent {
@@ -62,29 +68,33 @@ impl Event for AdminMsgEvent {
         bot_info: &BotInformation,
         api_tx: &mpsc::Sender<ApiAndOneshot>,
     ) -> Option<Self> {
-        let InternalEvent::OneBotEvent(json_str) = event else {
-            return None;
-        };
-        let json: Value = serde_json::from_str(json_str).ok()?;
-        let event = Self::new(api_tx.clone(), json).ok()?;
-
-        let mut admins = bot_info.deputy_admins.clone();
-        admins.insert(bot_info.main_admin);
+        let json = event.json()?;
+        let event = Self::new(api_tx.clone(), json.clone(), bot_info, event.msg_queue()).ok()?;
 
-        if !admins.contains(&event.sender.user_id) {
+        if !bot_info.is_admin(event.sender.user_id) {
             return None;
         }
 
         Some(event)
     }
+
+    fn event_group_id(&self) -> Option<i64> {
+        self.group_id
+    }
+
+    fn event_user_id(&self) -> Option<i64> {
+        Some(self.user_id)
+    }
 }
 
 impl AdminMsgEvent {
     fn new(
         api_tx: mpsc::Sender<ApiAndOneshot>,
-        json: Value,
+        json: Arc<Value>,
+        bot_info: &BotInformation,
+        msg_queue: MsgQueue,
     ) -> Result<AdminMsgEvent, EventBuildError> {
-        let msg_event = MsgEvent::new(api_tx, json)?;
+        let msg_event = MsgEvent::new(api_tx, json, bot_info, msg_queue)?;
 
         Ok(AdminMsgEvent {
             time: msg_event.time,
@@ -103,7 +113,9 @@ impl AdminMsgEvent {
             text: msg_event.text,
             human_text: msg_event.human_text,
             original_json: msg_event.original_json,
+            to_me: msg_event.to_me,
             api_tx: msg_event.api_tx,
+            msg_queue: msg_event.msg_queue,
         })
     }
 }
@@ -186,7 +198,13 @@ impl AdminMsgEvent {
         let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
 
-        send_api_request_with_forget(&self.api_tx, send_msg)
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(feature = "cqstring")]
@@ -206,9 +224,17 @@ impl AdminMsgEvent {
             Some(v) => format!(" {v}"),
             None => "".to_string(),
         };
-        let human_msg = Message::from(msg).to_human_string();
+        let msg = Message::from(msg);
+        let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
-        send_api_request_with_forget(&self.api_tx, send_msg);
+
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(not(feature = "cqstring"))]
@@ -232,7 +258,13 @@ impl AdminMsgEvent {
         let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
 
-        send_api_request_with_forget(&self.api_tx, send_msg);
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(feature = "cqstring")]
@@ -253,9 +285,17 @@ impl AdminMsgEvent {
             Some(v) => format!(" {v}"),
             None => "".to_string(),
         };
-        let human_msg = Message::from(msg).to_human_string();
+        let msg = Message::from(msg);
+        let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
-        send_api_request_with_forget(&self.api_tx, send_msg);
+
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(feature = "cqstring")]
@@ -276,7 +316,7 @@ impl AdminMsgEvent {
         };
         let msg = String::from(msg);
         info!("[reply] [to {message_type}{group_id} {nickname} {id}]: {msg}");
-        send_api_request_with_forget(&self.api_tx, send_msg);
+        crate::bot::runtimebot::send_api_request_with_forget(&self.api_tx, send_msg);
     }
 
     /// 便捷获取文本，如果没有文本则会返回空字符串，如果只需要借用，请使用 `borrow_text()`
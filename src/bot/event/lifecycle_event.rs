@@ -38,15 +38,11 @@ impl Event for LifecycleEvent {
     where
         Self: Sized,
     {
-        let InternalEvent::OneBotEvent(json_str) = event else {
+        let json = event.json()?;
+        if json.get("meta_event_type").and_then(|v| v.as_str()) != Some("lifecycle") {
             return None;
-        };
-        let event: LifecycleEvent = serde_json::from_str(json_str).ok()?;
-        if event.meta_event_type == "lifecycle" {
-            Some(event)
-        } else {
-            None
         }
+        LifecycleEvent::deserialize(json.as_ref()).ok()
     }
 }
 
@@ -3,17 +3,21 @@ use crate::bot::BotInformation;
 use crate::bot::event::InternalEvent;
 use crate::bot::message::cq_to_arr_inner;
 use crate::bot::plugin_builder::event::{Event, PostType};
-use crate::bot::runtimebot::{CanSendApi, send_api_request_with_forget};
+use crate::bot::runtimebot::{CanSendApi, MsgQueue, MsgTarget};
 use crate::error::EventBuildError;
 use crate::types::ApiAndOneshot;
 use crate::{
     Message,
-    bot::{SendApi, plugin_builder::event::Sex},
+    bot::{
+        SendApi,
+        plugin_builder::event::{Role, Sex},
+    },
 };
 use log::{debug, info};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::value::Index;
 use serde_json::{self, Value, json};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 #[cfg(feature = "cqstring")]
@@ -55,39 +59,56 @@ pub struct MsgEvent {
     pub text: Option<String>,
     /// 处理过的文本，会解析成人类易读形式，里面会包含\[image\]\[face\]等解析后字符串
     pub human_text: String,
-    /// 原始的onebot消息，已处理成json格式
-    pub original_json: Value,
+    /// 原始的onebot消息，已处理成json格式，同一消息周期内的事件共享同一份，
+    /// 需要 `Value` 时使用 `Value::clone(&event.original_json)`
+    pub original_json: Arc<Value>,
+    /// 是否是对 Bot 说的：私聊、at 了 Bot，或以 `Config::nicknames` 中的称呼开头，
+    /// 英文的称呼后不能紧接字母或数字
+    pub to_me: bool,
 
     /// 不推荐的消息发送方式
     pub api_tx: mpsc::Sender<ApiAndOneshot>,
+
+    /// 发送人是否为 Bot 的管理员
+    pub(crate) bot_admin: bool,
+    pub(crate) msg_queue: MsgQueue,
 }
 
 impl Event for MsgEvent {
     fn de(
         event: &InternalEvent,
-        _: &BotInformation,
+        bot_info: &BotInformation,
         api_tx: &mpsc::Sender<ApiAndOneshot>,
     ) -> Option<Self> {
-        let InternalEvent::OneBotEvent(json_str) = event else {
-            return None;
-        };
-        let json = serde_json::from_str(json_str).ok()?;
-        Self::new(api_tx.clone(), json).ok()
+        let json = event.json()?;
+        Self::new(api_tx.clone(), json.clone(), bot_info, event.msg_queue()).ok()
+    }
+
+    fn event_group_id(&self) -> Option<i64> {
+        self.group_id
+    }
+
+    fn event_user_id(&self) -> Option<i64> {
+        Some(self.user_id)
     }
 }
 
 impl MsgEvent {
     pub(crate) fn new(
         api_tx: mpsc::Sender<ApiAndOneshot>,
-        mut temp: Value,
+        temp: Arc<Value>,
+        bot_info: &BotInformation,
+        msg_queue: MsgQueue,
     ) -> Result<MsgEvent, EventBuildError> {
-        let temp_object = temp.as_object_mut().ok_or(EventBuildError::ParseError(
+        let temp_object = temp.as_object().ok_or(EventBuildError::ParseError(
             "Invalid JSON object".to_string(),
         ))?;
 
+        let get_string = |v: Option<&Value>| v.and_then(Value::as_str).map(String::from);
+
         let temp_sender = temp_object
-            .get_mut("sender")
-            .and_then(|v| v.as_object_mut())
+            .get("sender")
+            .and_then(|v| v.as_object())
             .ok_or(EventBuildError::ParseError(
                 "Invalid sender object".to_string(),
             ))?;
@@ -95,123 +116,61 @@ impl MsgEvent {
         let sender = {
             Sender {
                 user_id: temp_sender
-                    .get_mut("user_id")
+                    .get("user_id")
                     .and_then(|v| v.as_i64())
                     .ok_or(EventBuildError::ParseError("Invalid user_id".to_string()))?,
-                nickname: temp_sender.get_mut("nickname").and_then(|v| {
-                    if let Value::String(str) = v.take() {
-                        Some(str)
-                    } else {
-                        None
-                    }
-                }),
-                card: temp_sender.get_mut("card").and_then(|v| {
-                    if let Value::String(str) = v.take() {
-                        Some(str)
-                    } else {
-                        None
-                    }
-                }),
-                sex: if let Some(v) = temp_sender.get("sex").and_then(|v| v.as_str()) {
-                    match v {
-                        "male" => Some(Sex::Male),
-                        "female" => Some(Sex::Female),
-                        _ => None,
-                    }
-                } else {
-                    None
-                },
+                nickname: get_string(temp_sender.get("nickname")),
+                card: get_string(temp_sender.get("card")),
+                sex: temp_sender
+                    .get("sex")
+                    .and_then(|v| Sex::deserialize(v).ok()),
                 age: temp_sender
                     .get("age")
                     .and_then(|v| v.as_i64())
                     .map(|v| v as i32),
-                area: temp_sender.get_mut("area").and_then(|v| {
-                    if let Value::String(str) = v.take() {
-                        Some(str)
-                    } else {
-                        None
-                    }
-                }),
-                level: temp_sender.get_mut("level").and_then(|v| {
-                    if let Value::String(str) = v.take() {
-                        Some(str)
-                    } else {
-                        None
-                    }
-                }),
-                role: temp_sender.get_mut("role").and_then(|v| {
-                    if let Value::String(str) = v.take() {
-                        Some(str)
-                    } else {
-                        None
-                    }
-                }),
-                title: temp_sender.get_mut("title").and_then(|v| {
-                    if let Value::String(str) = v.take() {
-                        Some(str)
-                    } else {
-                        None
-                    }
-                }),
+                area: get_string(temp_sender.get("area")),
+                level: get_string(temp_sender.get("level")),
+                role: temp_sender
+                    .get("role")
+                    .and_then(|v| Role::deserialize(v).ok()),
+                title: get_string(temp_sender.get("title")),
             }
         };
 
         let group_id = temp_object.get("group_id").and_then(|v| v.as_i64());
 
-        let message = if temp_object
-            .get("message")
-            .and_then(|v| v.as_array())
-            .is_some()
-        {
-            let v = temp_object
-                .get("message")
-                .ok_or(EventBuildError::ParseError(
-                    "Missing 'message' field".to_string(),
-                ))?
-                .as_array()
-                .ok_or(EventBuildError::ParseError(
-                    "Invalid 'message' array".to_string(),
-                ))?
-                .to_vec();
-            Message::from_vec_segment_value(v)
-                .map_err(|e| EventBuildError::ParseError(format!("Parse error: {}", e)))?
-        } else {
-            let str_v = temp_object["message"]
-                .as_str()
-                .ok_or(format!(
-                    "message is not string:{:?}",
-                    temp_object["message"]
-                ))
-                .map_err(|e| EventBuildError::ParseError(format!("Parse error: {}", e)))?;
-            let arr_v = cq_to_arr_inner(str_v);
-            Message::from_vec_segment_value(arr_v)
-                .map_err(|e| EventBuildError::ParseError(format!("Parse error: {}", e)))?
+        let message = match temp_object.get("message") {
+            Some(Value::Array(v)) => Message::from_vec_segment_value(v.clone())
+                .map_err(|e| EventBuildError::ParseError(format!("Parse error: {}", e)))?,
+            Some(Value::String(str_v)) => {
+                let arr_v = cq_to_arr_inner(str_v);
+                Message::from_vec_segment_value(arr_v)
+                    .map_err(|e| EventBuildError::ParseError(format!("Parse error: {}", e)))?
+            }
+            other => {
+                return Err(EventBuildError::ParseError(format!(
+                    "Parse error: message is not string:{:?}",
+                    other
+                )));
+            }
         };
 
-        let anonymous: Option<Anonymous> =
-            if temp_object.get("anonymous").is_none_or(|v| v.is_null()) {
-                None
-            } else {
-                let anonymous = temp_object
-                    .get_mut("anonymous")
-                    .ok_or(EventBuildError::ParseError(
-                        "Invalid anonymous field".to_string(),
-                    ))?
-                    .take();
-                Some(
-                    serde_json::from_value(anonymous)
-                        .map_err(|e| EventBuildError::ParseError(e.to_string()))?,
-                )
-            };
+        let anonymous: Option<Anonymous> = match temp_object.get("anonymous") {
+            None | Some(Value::Null) => None,
+            Some(anonymous) => Some(
+                Anonymous::deserialize(anonymous)
+                    .map_err(|e| EventBuildError::ParseError(e.to_string()))?,
+            ),
+        };
 
         let text = {
             let mut text_vec = Vec::new();
             for msg in message.iter() {
-                if msg.type_ == "text" {
-                    if let Some(text_value) = msg.data.get("text").and_then(|v| v.as_str()) {
-                        text_vec.push(text_value);
-                    }
-                };
+                if msg.type_ == "text"
+                    && let Some(text_value) = msg.data.get("text").and_then(|v| v.as_str())
+                {
+                    text_vec.push(text_value);
+                }
             }
             if !text_vec.is_empty() {
                 Some(text_vec.join("\n").trim().to_string())
@@ -220,41 +179,33 @@ impl MsgEvent {
             }
         };
 
+        let self_id = temp_object
+            .get("self_id")
+            .and_then(|v| v.as_i64())
+            .ok_or(EventBuildError::ParseError("Invalid self_id".to_string()))?;
+
+        let to_me = group_id.is_none()
+            || message.is_at(self_id)
+            || bot_info
+                .nicknames
+                .iter()
+                .any(|name| starts_with_name(&message, name));
+
         let event = MsgEvent {
             human_text: message.to_human_string(),
             time: temp_object
                 .get("time")
                 .and_then(|v| v.as_i64())
                 .ok_or(EventBuildError::ParseError("Invalid time".to_string()))?,
-            self_id: temp_object
-                .get("self_id")
-                .and_then(|v| v.as_i64())
-                .ok_or(EventBuildError::ParseError("Invalid self_id".to_string()))?,
+            self_id,
             post_type: temp_object
-                .get_mut("post_type")
-                .and_then(|v| serde_json::from_value::<PostType>(v.take()).ok())
+                .get("post_type")
+                .and_then(|v| PostType::deserialize(v).ok())
                 .ok_or(EventBuildError::ParseError("Invalid post_type".to_string()))?,
-            message_type: temp_object
-                .get_mut("message_type")
-                .and_then(|v| {
-                    if let Value::String(str) = v.take() {
-                        Some(str)
-                    } else {
-                        None
-                    }
-                })
-                .ok_or(EventBuildError::ParseError(
-                    "Invalid message_type".to_string(),
-                ))?,
-            sub_type: temp_object
-                .get_mut("sub_type")
-                .and_then(|v| {
-                    if let Value::String(str) = v.take() {
-                        Some(str)
-                    } else {
-                        None
-                    }
-                })
+            message_type: get_string(temp_object.get("message_type")).ok_or(
+                EventBuildError::ParseError("Invalid message_type".to_string()),
+            )?,
+            sub_type: get_string(temp_object.get("sub_type"))
                 .ok_or(EventBuildError::ParseError("Invalid sub_type".to_string()))?,
             message,
             message_id: temp_object
@@ -269,27 +220,21 @@ impl MsgEvent {
                 .and_then(|v| v.as_i64())
                 .ok_or(EventBuildError::ParseError("Invalid user_id".to_string()))?,
             anonymous,
-            raw_message: temp_object
-                .get_mut("raw_message")
-                .and_then(|v| {
-                    if let Value::String(str) = v.take() {
-                        Some(str)
-                    } else {
-                        None
-                    }
-                })
-                .ok_or(EventBuildError::ParseError(
-                    "Invalid raw_message".to_string(),
-                ))?,
+            raw_message: get_string(temp_object.get("raw_message")).ok_or(
+                EventBuildError::ParseError("Invalid raw_message".to_string()),
+            )?,
             font: temp_object
                 .get("font")
                 .and_then(|v| v.as_i64())
                 .ok_or(EventBuildError::ParseError("Invalid font".to_string()))?
                 as i32,
+            bot_admin: bot_info.is_admin(sender.user_id),
+            msg_queue,
             sender,
             api_tx,
             text,
             original_json: temp,
+            to_me,
         };
         debug!("{:?}", event);
         Ok(event)
@@ -374,7 +319,13 @@ impl MsgEvent {
         let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
 
-        send_api_request_with_forget(&self.api_tx, send_msg)
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(feature = "cqstring")]
@@ -394,9 +345,17 @@ impl MsgEvent {
             Some(v) => format!(" {v}"),
             None => "".to_string(),
         };
-        let human_msg = Message::from(msg).to_human_string();
+        let msg = Message::from(msg);
+        let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
-        send_api_request_with_forget(&self.api_tx, send_msg);
+
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(not(feature = "cqstring"))]
@@ -420,7 +379,13 @@ impl MsgEvent {
         let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
 
-        send_api_request_with_forget(&self.api_tx, send_msg);
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(feature = "cqstring")]
@@ -441,9 +406,17 @@ impl MsgEvent {
             Some(v) => format!(" {v}"),
             None => "".to_string(),
         };
-        let human_msg = Message::from(msg).to_human_string();
+        let msg = Message::from(msg);
+        let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
-        send_api_request_with_forget(&self.api_tx, send_msg);
+
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(feature = "cqstring")]
@@ -464,7 +437,7 @@ impl MsgEvent {
         };
         let msg = String::from(msg);
         info!("[reply] [to {message_type}{group_id} {nickname} {id}]: {msg}");
-        send_api_request_with_forget(&self.api_tx, send_msg);
+        crate::bot::runtimebot::send_api_request_with_forget(&self.api_tx, send_msg);
     }
 
     /// 便捷获取文本，如果没有文本则会返回空字符串，如果只需要借用，请使用 `borrow_text()`
@@ -496,6 +469,41 @@ impl MsgEvent {
     pub fn is_private(&self) -> bool {
         self.group_id.is_none()
     }
+
+    /// 发送人是否为群主
+    pub fn sender_is_group_owner(&self) -> bool {
+        self.sender.is_group_owner()
+    }
+
+    /// 发送人是否为群管理员，群主也算
+    pub fn sender_is_group_admin(&self) -> bool {
+        self.sender.is_group_admin()
+    }
+
+    /// 发送人是否为 Bot 的主管理员或副管理员
+    pub fn sender_is_bot_admin(&self) -> bool {
+        self.bot_admin
+    }
+
+    /// 消息中是否 at 了 Bot
+    pub fn is_at_me(&self) -> bool {
+        self.message.is_at(self.self_id)
+    }
+
+    /// 消息中是否 at 了全体成员
+    pub fn is_at_all(&self) -> bool {
+        self.message.is_at_all()
+    }
+
+    /// 消息中 at 的所有用户，按出现顺序去重，不含 at 全体成员
+    pub fn mentioned_users(&self) -> Vec<i64> {
+        self.message.mentioned_users()
+    }
+
+    /// 消息中的文字，去掉 at、引用等其他 segment，用于获取命令
+    pub fn text_without_mentions(&self) -> String {
+        self.message.text_without_mentions()
+    }
 }
 
 impl CanSendApi for MsgEvent {
@@ -503,3 +511,28 @@ impl CanSendApi for MsgEvent {
         &self.api_tx
     }
 }
+
+/// 消息的文字去掉开头的空白后以称呼 `name` 开头，逐个比较文字 segment 中的字符，不拼接文字
+///
+/// 称呼的最后一个字与后面的字都是 ASCII 字母或数字时不算，例如称呼 `kovi` 不匹配 `kovihelp`，
+/// 中文的称呼后面可以直接接内容，例如 `小助手帮我…`
+fn starts_with_name(message: &Message, name: &str) -> bool {
+    let mut chars = message
+        .iter()
+        .filter(|segment| segment.type_ == "text")
+        .filter_map(|segment| segment.data.get("text").and_then(Value::as_str))
+        .flat_map(str::chars)
+        .skip_while(|c| c.is_whitespace());
+
+    let mut last = None;
+    for expected in name.chars() {
+        if chars.next() != Some(expected) {
+            return false;
+        }
+        last = Some(expected);
+    }
+    match (last, chars.next()) {
+        (Some(last), Some(next)) => !(last.is_ascii_alphanumeric() && next.is_ascii_alphanumeric()),
+        _ => true,
+    }
+}
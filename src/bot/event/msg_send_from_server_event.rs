@@ -3,7 +3,7 @@ use crate::MsgEvent;
 use crate::bot::BotInformation;
 use crate::bot::event::InternalEvent;
 use crate::bot::plugin_builder::event::{Event, PostType};
-use crate::bot::runtimebot::{CanSendApi, send_api_request_with_forget};
+use crate::bot::runtimebot::{CanSendApi, MsgQueue, MsgTarget};
 use crate::error::EventBuildError;
 use crate::types::ApiAndOneshot;
 use crate::{Message, bot::SendApi};
@@ -11,6 +11,7 @@ use log::info;
 use serde::Serialize;
 use serde_json::value::Index;
 use serde_json::{self, Value, json};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 #[cfg(feature = "cqstring")]
@@ -49,39 +50,49 @@ pub struct MsgSendFromServerEvent {
     pub text: Option<String>,
     /// 处理过的文本，会解析成人类易读形式，里面会包含\[image\]\[face\]等解析后字符串
     pub human_text: String,
-    /// 原始的onebot消息，已处理成json格式
-    pub original_json: Value,
+    /// 原始的onebot消息，已处理成json格式，同一消息周期内的事件共享同一份，
+    /// 需要 `Value` 时使用 `Value::clone(&event.original_json)`
+    pub original_json: Arc<Value>,
 
     /// 不推荐的消息发送方式
     pub api_tx: mpsc::Sender<ApiAndOneshot>,
+
+    pub(crate) msg_queue: MsgQueue,
 }
 
 impl Event for MsgSendFromServerEvent {
     fn de(
         event: &InternalEvent,
-        _: &BotInformation,
+        bot_info: &BotInformation,
         api_tx: &mpsc::Sender<ApiAndOneshot>,
     ) -> Option<Self> {
-        let InternalEvent::OneBotEvent(json_str) = event else {
-            return None;
-        };
-        if !json_str.contains("message_sent") {
+        let json = event.json()?;
+        if json.get("post_type").and_then(Value::as_str) != Some("message_sent") {
             return None;
         }
 
-        let json = serde_json::from_str(json_str).ok()?;
-        let event = Self::new(api_tx.clone(), json).ok()?;
+        let event = Self::new(api_tx.clone(), json.clone(), bot_info, event.msg_queue()).ok()?;
 
         Some(event)
     }
+
+    fn event_group_id(&self) -> Option<i64> {
+        self.group_id
+    }
+
+    fn event_user_id(&self) -> Option<i64> {
+        Some(self.user_id)
+    }
 }
 
 impl MsgSendFromServerEvent {
     fn new(
         api_tx: mpsc::Sender<ApiAndOneshot>,
-        json: Value,
+        json: Arc<Value>,
+        bot_info: &BotInformation,
+        msg_queue: MsgQueue,
     ) -> Result<MsgSendFromServerEvent, EventBuildError> {
-        let msg_event = MsgEvent::new(api_tx, json)?;
+        let msg_event = MsgEvent::new(api_tx, json, bot_info, msg_queue)?;
 
         if msg_event.post_type != PostType::MessageSent {
             return Err(EventBuildError::ParseError(
@@ -107,6 +118,7 @@ impl MsgSendFromServerEvent {
             human_text: msg_event.human_text,
             original_json: msg_event.original_json,
             api_tx: msg_event.api_tx,
+            msg_queue: msg_event.msg_queue,
         })
     }
 }
@@ -189,7 +201,13 @@ impl MsgSendFromServerEvent {
         let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
 
-        send_api_request_with_forget(&self.api_tx, send_msg)
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(feature = "cqstring")]
@@ -209,9 +227,17 @@ impl MsgSendFromServerEvent {
             Some(v) => format!(" {v}"),
             None => "".to_string(),
         };
-        let human_msg = Message::from(msg).to_human_string();
+        let msg = Message::from(msg);
+        let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
-        send_api_request_with_forget(&self.api_tx, send_msg);
+
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(not(feature = "cqstring"))]
@@ -235,7 +261,13 @@ impl MsgSendFromServerEvent {
         let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
 
-        send_api_request_with_forget(&self.api_tx, send_msg);
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(feature = "cqstring")]
@@ -256,9 +288,17 @@ impl MsgSendFromServerEvent {
             Some(v) => format!(" {v}"),
             None => "".to_string(),
         };
-        let human_msg = Message::from(msg).to_human_string();
+        let msg = Message::from(msg);
+        let human_msg = msg.to_human_string();
         info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
-        send_api_request_with_forget(&self.api_tx, send_msg);
+
+        self.msg_queue.send(
+            &self.api_tx,
+            MsgTarget::new(self.group_id, self.user_id),
+            &msg,
+            send_msg,
+            Some(self.self_id),
+        );
     }
 
     #[cfg(feature = "cqstring")]
@@ -279,7 +319,7 @@ impl MsgSendFromServerEvent {
         };
         let msg = String::from(msg);
         info!("[reply] [to {message_type}{group_id} {nickname} {id}]: {msg}");
-        send_api_request_with_forget(&self.api_tx, send_msg);
+        crate::bot::runtimebot::send_api_request_with_forget(&self.api_tx, send_msg);
     }
 
     /// 便捷获取文本，如果没有文本则会返回空字符串，如果只需要借用，请使用 `borrow_text()`
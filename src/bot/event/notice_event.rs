@@ -7,7 +7,9 @@ use crate::{
     error::EventBuildError,
     types::ApiAndOneshot,
 };
+use serde::Deserialize;
 use serde_json::{Value, value::Index};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 #[derive(Debug, Clone)]
@@ -21,8 +23,9 @@ pub struct NoticeEvent {
     /// 通知类型
     pub notice_type: String,
 
-    /// 原始的onebot消息，已处理成json格式
-    pub original_json: Value,
+    /// 原始的onebot消息，已处理成json格式，同一消息周期内的事件共享同一份，
+    /// 需要 `Value` 时使用 `Value::clone(&event.original_json)`
+    pub original_json: Arc<Value>,
 }
 impl Event for NoticeEvent {
     fn de(
@@ -30,17 +33,21 @@ impl Event for NoticeEvent {
         _: &BotInformation,
         _: &mpsc::Sender<ApiAndOneshot>,
     ) -> Option<Self> {
-        let InternalEvent::OneBotEvent(json_str) = event else {
-            return None;
-        };
-        Self::new(json_str).ok()
+        let json = event.json()?;
+        Self::new(json.clone()).ok()
+    }
+
+    fn event_group_id(&self) -> Option<i64> {
+        self.original_json.get("group_id")?.as_i64()
+    }
+
+    fn event_user_id(&self) -> Option<i64> {
+        self.original_json.get("user_id")?.as_i64()
     }
 }
 
 impl NoticeEvent {
-    pub(crate) fn new(msg: &str) -> Result<NoticeEvent, EventBuildError> {
-        let temp: Value =
-            serde_json::from_str(msg).map_err(|e| EventBuildError::ParseError(e.to_string()))?;
+    pub(crate) fn new(temp: Arc<Value>) -> Result<NoticeEvent, EventBuildError> {
         let time = temp
             .get("time")
             .and_then(Value::as_i64)
@@ -51,7 +58,7 @@ impl NoticeEvent {
             .ok_or(EventBuildError::ParseError("self_id".to_string()))?;
         let post_type = temp
             .get("post_type")
-            .and_then(|v| serde_json::from_value::<PostType>(v.clone()).ok())
+            .and_then(|v| PostType::deserialize(v).ok())
             .ok_or(EventBuildError::ParseError("Invalid post_type".to_string()))?;
         let notice_type = temp
             .get("notice_type")
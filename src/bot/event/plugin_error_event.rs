@@ -0,0 +1,40 @@
+use crate::{
+    bot::{BotInformation, event::InternalEvent, plugin_builder::event::Event},
+    types::ApiAndOneshot,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+/// 插件运行出错时由 Kovi 发出的事件
+///
+/// 出错插件自身的此事件监听也会收到，但处理此事件时出的错不会再次发出此事件。
+#[derive(Debug, Clone)]
+pub struct PluginErrorEvent {
+    /// 出错的插件
+    pub plugin_name: String,
+    /// 出错的类型
+    pub kind: PluginErrorKind,
+    /// 错误信息
+    pub message: String,
+    /// panic 时捕获的 backtrace，需要设置 `RUST_BACKTRACE` 环境变量才会有
+    pub backtrace: Option<String>,
+    /// 插件是否因此被自动关闭
+    pub disabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PluginErrorKind {
+    /// 处理函数 panic
+    Panic,
+    /// 处理函数返回了 `Err`
+    Error,
+    /// 处理函数超时
+    Timeout,
+}
+
+impl Event for PluginErrorEvent {
+    /// 此事件由 Kovi 直接交给监听，不从 OneBot 事件解析
+    fn de(_: &InternalEvent, _: &BotInformation, _: &mpsc::Sender<ApiAndOneshot>) -> Option<Self> {
+        None
+    }
+}
@@ -0,0 +1,155 @@
+use crate::bot::event::event_message;
+use crate::bot::plugin_builder::event::Event;
+use std::ops::Not;
+
+/// 监听的过滤条件，在分发时检查，不满足的事件不会产生任务
+///
+/// 不是消息事件时，除 `MsgFilter::Not` 以外的条件都不满足。
+///
+/// ```no_run
+/// use kovi::PluginBuilder;
+/// use kovi::plugin::MsgFilter;
+///
+/// async fn start() {
+///     let filter = MsgFilter::InGroup(123456)
+///         .or(MsgFilter::PrivateOnly)
+///         .and(MsgFilter::starts_with("/"))
+///         .and(!MsgFilter::FromUser(10000));
+///     PluginBuilder::on_msg_filtered(filter, |event| async move {});
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MsgFilter {
+    /// 此群的消息
+    InGroup(i64),
+    /// 此用户发送的消息
+    FromUser(i64),
+    /// 群消息
+    GroupOnly,
+    /// 私聊消息
+    PrivateOnly,
+    /// 文字以此开头，忽略开头的空白与文字以外的 segment
+    StartsWith(String),
+    /// 含有此类型的 segment，例如 `image`
+    HasSegment(String),
+    And(Box<MsgFilter>, Box<MsgFilter>),
+    Or(Box<MsgFilter>, Box<MsgFilter>),
+    Not(Box<MsgFilter>),
+}
+
+impl MsgFilter {
+    pub fn starts_with<S: Into<String>>(prefix: S) -> Self {
+        MsgFilter::StartsWith(prefix.into())
+    }
+
+    pub fn has_segment<S: Into<String>>(type_: S) -> Self {
+        MsgFilter::HasSegment(type_.into())
+    }
+
+    /// 同时满足两个条件
+    pub fn and(self, other: MsgFilter) -> Self {
+        MsgFilter::And(Box::new(self), Box::new(other))
+    }
+
+    /// 满足任一条件
+    pub fn or(self, other: MsgFilter) -> Self {
+        MsgFilter::Or(Box::new(self), Box::new(other))
+    }
+
+    /// 事件是否满足条件
+    pub fn check(&self, event: &dyn Event) -> bool {
+        let msg = event_message(event).map(|(msg, _)| msg);
+        match self {
+            MsgFilter::InGroup(group_id) => {
+                msg.is_some() && event.event_group_id() == Some(*group_id)
+            }
+            MsgFilter::FromUser(user_id) => {
+                msg.is_some() && event.event_user_id() == Some(*user_id)
+            }
+            MsgFilter::GroupOnly => msg.is_some() && event.event_group_id().is_some(),
+            MsgFilter::PrivateOnly => msg.is_some() && event.event_group_id().is_none(),
+            MsgFilter::StartsWith(prefix) => {
+                msg.is_some_and(|msg| msg.plain_text().trim_start().starts_with(prefix.as_str()))
+            }
+            MsgFilter::HasSegment(type_) => {
+                msg.is_some_and(|msg| msg.iter().any(|segment| segment.type_ == *type_))
+            }
+            MsgFilter::And(a, b) => a.check(event) && b.check(event),
+            MsgFilter::Or(a, b) => a.check(event) || b.check(event),
+            MsgFilter::Not(filter) => !filter.check(event),
+        }
+    }
+}
+
+impl Not for MsgFilter {
+    type Output = MsgFilter;
+
+    /// 不满足条件
+    fn not(self) -> Self::Output {
+        MsgFilter::Not(Box::new(self))
+    }
+}
+
+#[test]
+fn filters_compose() {
+    use crate::bot::{BotInformation, Host, Server};
+    use crate::event::MsgEvent;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let bot_info = BotInformation {
+        main_admin: 2,
+        deputy_admins: Default::default(),
+        server: Server::new(
+            Host::Domain("localhost".to_string()),
+            8081,
+            String::new(),
+            false,
+        ),
+        roles: Default::default(),
+        media: Default::default(),
+        split: Default::default(),
+        nicknames: Vec::new(),
+    };
+    let event = |group_id: Option<i64>, user_id: i64, message: serde_json::Value| {
+        let json = json!({
+            "time": 0, "self_id": 1, "post_type": "message",
+            "message_type": if group_id.is_some() { "group" } else { "private" },
+            "sub_type": "normal", "message_id": 1, "group_id": group_id, "user_id": user_id,
+            "message": message, "raw_message": "", "font": 0,
+            "sender": {"user_id": user_id}
+        });
+        let (api_tx, _api_rx) = tokio::sync::mpsc::channel(1);
+        MsgEvent::new(api_tx, Arc::new(json), &bot_info, Default::default())
+            .expect("valid message event")
+    };
+
+    let command = event(
+        Some(3),
+        2,
+        json!([
+            {"type": "at", "data": {"qq": "1"}},
+            {"type": "text", "data": {"text": " /help"}},
+        ]),
+    );
+    let image = event(
+        None,
+        4,
+        json!([{"type": "image", "data": {"file": "a.png"}}]),
+    );
+
+    let filter = MsgFilter::InGroup(3)
+        .or(MsgFilter::PrivateOnly)
+        .and(MsgFilter::starts_with("/"));
+    assert!(filter.check(&command));
+    assert!(!filter.check(&image));
+    assert!(
+        MsgFilter::has_segment("image")
+            .and(MsgFilter::PrivateOnly)
+            .check(&image)
+    );
+    assert!(!MsgFilter::has_segment("image").check(&command));
+    assert!(!(!MsgFilter::FromUser(2)).check(&command));
+    assert!((!MsgFilter::FromUser(2)).check(&image));
+    assert!(!MsgFilter::GroupOnly.check(&image));
+}
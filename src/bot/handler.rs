@@ -1,19 +1,26 @@
 use crate::{
+    bot::runtimebot::kovi_api::disable_plugin,
     bot::{
         plugin_builder::{
-            ListenInner,
+            ListenInner, Matched,
             event::{Event, lifecycle_event::LifecycleEvent},
         },
         *,
     },
-    event::InternalEvent,
-    plugin::PLUGIN_NAME,
-    types::ApiAndOneshot,
+    event::{InternalEvent, PluginErrorEvent},
+    plugin::{
+        PLUGIN_NAME,
+        cancel::{CANCEL_GRACE, CANCEL_TOKEN, CancellationToken},
+        health::ErrorReporter,
+        limiter::Limiter,
+    },
+    types::{ApiAndOneshot, PinFut},
 };
 use log::info;
 use parking_lot::RwLock;
 use plugin_builder::event::MsgEvent;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Kovi内部事件
 pub(crate) enum InternalInternalEvent {
@@ -23,57 +30,91 @@ pub(crate) enum InternalInternalEvent {
 
 pub(crate) enum KoviEvent {
     Drop,
+    /// 插件出错，`dispatch` 为 `false` 时只处理自动关闭，不分发 `PluginErrorEvent`
+    PluginError {
+        event: PluginErrorEvent,
+        dispatch: bool,
+    },
 }
 
 impl Bot {
     pub(crate) async fn handler_event(
         bot: Arc<RwLock<Self>>,
+        snapshot: Arc<SnapshotCell>,
         event: InternalInternalEvent,
         api_tx: mpsc::Sender<ApiAndOneshot>,
     ) {
         match event {
-            InternalInternalEvent::KoviEvent(event) => Self::handle_kovi_event(bot, event).await,
+            InternalInternalEvent::KoviEvent(event) => {
+                Self::handle_kovi_event(bot, snapshot, event, api_tx).await
+            }
             InternalInternalEvent::OneBotEvent(msg) => {
-                Self::handler_internal_event(bot, msg, api_tx).await
+                Self::handler_internal_event(snapshot.load(), msg, api_tx)
             }
         }
     }
 
-    pub(crate) async fn handle_kovi_event(bot: Arc<RwLock<Self>>, event: KoviEvent) {
-        let drop_task = {
-            let mut bot_write = bot.write();
-            match event {
-                KoviEvent::Drop => {
-                    #[cfg(any(feature = "save_plugin_status", feature = "save_bot_admin"))]
+    pub(crate) async fn handle_kovi_event(
+        bot: Arc<RwLock<Self>>,
+        snapshot: Arc<SnapshotCell>,
+        event: KoviEvent,
+        api_tx: mpsc::Sender<ApiAndOneshot>,
+    ) {
+        match event {
+            KoviEvent::Drop => {
+                let drop_task = {
+                    let mut bot_write = bot.write();
+                    #[cfg(any(
+                        feature = "save_plugin_status",
+                        feature = "save_bot_admin",
+                        feature = "save_bot_roles"
+                    ))]
                     bot_write.save_bot_status();
                     let mut task_vec = Vec::new();
                     for plugin in bot_write.plugins.values_mut() {
                         task_vec.push(plugin.shutdown());
                     }
-                    Some(task_vec)
+                    bot_write.publish_snapshot();
+                    task_vec
+                };
+                for task in drop_task {
+                    let _ = task.await;
                 }
             }
-        };
-        if let Some(drop_task) = drop_task {
-            for task in drop_task {
-                let _ = task.await;
+            KoviEvent::PluginError { event, dispatch } => {
+                if event.disabled
+                    && let Err(e) = disable_plugin(bot, &event.plugin_name)
+                {
+                    log::error!("Failed to disable plugin '{}': {e}", event.plugin_name);
+                }
+                if dispatch {
+                    Self::dispatch_plugin_error(&snapshot.load(), event, &api_tx);
+                }
             }
         }
     }
 
-    async fn handler_internal_event(
-        bot: Arc<RwLock<Self>>,
-        msg: InternalEvent,
+    /// 分发事件，只使用传入的快照，不持有 `Bot` 的锁
+    /// 解析并分发 OneBot 事件，监听的处理会另开任务运行，此函数不会等待
+    ///
+    /// 需要按事件到达的顺序调用，顺序处理的排队依赖于此。
+    pub(crate) fn handler_internal_event(
+        snapshot: Arc<BotSnapshot>,
+        mut msg: InternalEvent,
         api_tx: mpsc::Sender<ApiAndOneshot>,
     ) {
         // debug!("{msg_json}");
 
-        let bot_read = bot.read();
+        if let InternalEvent::OneBotEvent(event) = &mut msg {
+            event.msg_queue = snapshot.msg_queue.clone();
+        }
+
+        let bot_info = &snapshot.information;
 
         let mut cache: ahash::HashMap<std::any::TypeId, Option<Arc<dyn Event>>> =
             ahash::HashMap::default();
 
-        if let Some(lifecycle_event) = LifecycleEvent::de(&msg, &bot_read.information, &api_tx) {
+        if let Some(lifecycle_event) = LifecycleEvent::de(&msg, bot_info, &api_tx) {
             tokio::spawn(LifecycleEvent::handler_lifecycle(api_tx.clone()));
             cache.insert(
                 std::any::TypeId::of::<LifecycleEvent>(),
@@ -81,76 +122,191 @@ impl Bot {
             );
         };
 
-        let msg_event = MsgEvent::de(&msg, &bot_read.information, &api_tx);
+        if let Some(event) = MsgEvent::de(&msg, bot_info, &api_tx) {
+            info!(
+                "[{message_type}{group_id}{nickname} {id}]: {text}",
+                message_type = event.message_type,
+                group_id = match event.group_id {
+                    Some(id) => id.to_string(),
+                    None => "".to_string(),
+                },
+                nickname = match &event.sender.nickname {
+                    Some(nickname) => nickname,
+                    None => "",
+                },
+                id = event.sender.user_id,
+                text = event.message.to_human_string()
+            );
+
+            cache.insert(std::any::TypeId::of::<MsgEvent>(), Some(Arc::new(event)));
+        }
 
-        // 这里在 没有 plugin-access-control 会警告所以用 _
-        let _msg_sevent_opt = match msg_event {
-            Some(event) => {
-                let event = Arc::new(event);
+        Self::dispatch(&snapshot, &api_tx, |listen| {
+            cache
+                .entry(listen.type_id)
+                .or_insert_with(|| (listen.type_de)(&msg, bot_info, &api_tx))
+                .clone()
+        });
+    }
 
-                info!(
-                    "[{message_type}{group_id}{nickname} {id}]: {text}",
-                    message_type = event.message_type,
-                    group_id = match event.group_id {
-                        Some(id) => id.to_string(),
-                        None => "".to_string(),
-                    },
-                    nickname = match &event.sender.nickname {
-                        Some(nickname) => nickname,
-                        None => "",
-                    },
-                    id = event.sender.user_id,
-                    text = event.message.to_human_string()
-                );
+    /// 分发 Kovi 发出的 `PluginErrorEvent`，只有它的监听会收到，不经过其他事件的解析
+    fn dispatch_plugin_error(
+        snapshot: &BotSnapshot,
+        event: PluginErrorEvent,
+        api_tx: &mpsc::Sender<ApiAndOneshot>,
+    ) {
+        let event: Arc<dyn Event> = Arc::new(event);
+        Self::dispatch(snapshot, api_tx, |listen| {
+            (listen.type_id == std::any::TypeId::of::<PluginErrorEvent>()).then(|| event.clone())
+        });
+    }
+
+    /// 把事件交给所有开启的插件中能收到它的监听，`resolve` 返回监听对应的事件
+    fn dispatch(
+        snapshot: &BotSnapshot,
+        api_tx: &mpsc::Sender<ApiAndOneshot>,
+        mut resolve: impl FnMut(&ListenInner) -> Option<Arc<dyn Event>>,
+    ) {
+        let bot_info = &snapshot.information;
 
-                cache.insert(std::any::TypeId::of::<MsgEvent>(), Some(event.clone()));
+        #[cfg(feature = "plugin-access-control")]
+        let now = chrono::Local::now().timestamp();
 
-                Some(event)
+        for (name, plugin) in snapshot.plugins.iter() {
+            if !*plugin.enabled.borrow() {
+                continue;
             }
-            None => None,
-        };
 
-        for (name, plugin) in bot_read.plugins.iter() {
-            #[cfg(feature = "plugin-access-control")]
-            if let Some(event) = &_msg_sevent_opt {
-                // 判断是否黑白名单
-                if !is_access(plugin, event) {
+            for listen in &plugin.listen.list {
+                let Some(cache_event) = resolve(listen) else {
+                    continue;
+                };
+
+                // 插件在此群或对此用户关闭
+                if !plugin
+                    .disabled_in
+                    .allows(cache_event.event_group_id(), cache_event.event_user_id())
+                {
                     continue;
                 }
-            }
 
-            let name_ = Arc::new(name.clone());
+                // 判断是否在全局黑名单与插件的黑白名单
+                #[cfg(feature = "plugin-access-control")]
+                if is_blacklisted(&snapshot.blacklist, &*cache_event, now)
+                    || !is_access(plugin, &*cache_event, now)
+                {
+                    continue;
+                }
 
-            for listen in &plugin.listen.list {
-                let name = name_.clone();
-                let api_tx = api_tx.clone();
-
-                let cache_event = match cache.get(&listen.type_id) {
-                    Some(event) => match event {
-                        None => {
-                            continue;
-                        }
-                        Some(event) => event.clone(),
+                if !listen
+                    .filters
+                    .iter()
+                    .all(|filter| filter(&*cache_event, bot_info))
+                {
+                    continue;
+                }
+
+                // 匹配只在这里进行一次，结果交给处理函数
+                let matched: Matched = match &listen.matcher {
+                    Some(matcher) => match matcher(&*cache_event) {
+                        Some(matched) => matched,
+                        None => continue,
                     },
-                    None => {
-                        let event_opt = (listen.type_de)(&msg, &bot_read.information, &api_tx);
-                        cache.insert(listen.type_id, event_opt.clone());
-                        match event_opt {
-                            Some(event) => event,
-                            None => continue,
-                        }
-                    }
+                    None => Box::new(()),
                 };
 
+                let sequencer = listen.sequencer.as_ref().or(plugin.sequencer.as_ref());
+                let timeout = listen.timeout.or(plugin.timeout);
                 let listen = listen.clone();
                 let enabled = plugin.enabled.subscribe();
 
-                RT.spawn(async move {
-                    tokio::select! {
-                        _ = PLUGIN_NAME.scope(name, Self::handle_listen(listen, cache_event)) => {}
-                        _ = monitor_enabled_state(enabled) => {}
+                let reporter = ErrorReporter {
+                    plugin_name: name.clone(),
+                    health: plugin.health.clone(),
+                    event_tx: snapshot.event_tx.clone(),
+                    dispatch: listen.type_id != std::any::TypeId::of::<PluginErrorEvent>(),
+                    api_tx: Some(api_tx.clone()),
+                    enabled: plugin.enabled.clone(),
+                };
+
+                // 排队的位置在分发时就确定，与事件到达的顺序一致
+                let turn = sequencer.and_then(|sequencer| sequencer.order(&*cache_event));
+                let task: PinFut = Box::pin(listen_task(
+                    reporter,
+                    listen,
+                    cache_event,
+                    matched,
+                    timeout,
+                    enabled,
+                ));
+
+                match turn {
+                    // 等同分组的上一个处理结束后才占用并发位置，不会占着位置空等
+                    Some(turn) => {
+                        let limiter = plugin.limiter.clone();
+                        RT.spawn(async move {
+                            let done = turn.wait().await;
+                            run_task(
+                                limiter.as_ref(),
+                                Box::pin(async move {
+                                    let _done = done;
+                                    task.await;
+                                }),
+                            );
+                        });
                     }
-                });
+                    None => run_task(plugin.limiter.as_ref(), task),
+                }
+            }
+        }
+
+        fn run_task(limiter: Option<&Arc<Limiter>>, task: PinFut) {
+            match limiter {
+                Some(limiter) => limiter.submit(task),
+                None => {
+                    RT.spawn(task);
+                }
+            }
+        }
+
+        async fn listen_task(
+            reporter: ErrorReporter,
+            listen: Arc<ListenInner>,
+            event: Arc<dyn Event>,
+            matched: Matched,
+            timeout: Option<Duration>,
+            enabled: watch::Receiver<bool>,
+        ) {
+            let name = reporter.plugin_name.clone();
+            let type_name = listen.type_name;
+            let (cancel_tx, token) = CancellationToken::new();
+            let handle = CANCEL_TOKEN.scope(token, async {
+                let result =
+                    reporter.catch_panic(Bot::handle_listen(listen, event.clone(), matched));
+                if let Some(Some(error)) = result.await {
+                    reporter.handler_error(&*event, type_name, error).await;
+                }
+            });
+            let handle = async {
+                let Some(timeout) = timeout else {
+                    return handle.await;
+                };
+                tokio::pin!(handle);
+                if tokio::time::timeout(timeout, &mut handle).await.is_ok() {
+                    return;
+                }
+                // 超时后先通知处理函数收尾，收尾时间过后直接中断
+                let _ = cancel_tx.send(true);
+                let finished = tokio::time::timeout(CANCEL_GRACE, &mut handle)
+                    .await
+                    .is_ok();
+                reporter
+                    .handler_timeout(&*event, type_name, timeout, finished)
+                    .await;
+            };
+            tokio::select! {
+                _ = PLUGIN_NAME.scope(name, handle) => {}
+                _ = monitor_enabled_state(enabled) => {}
             }
         }
 
@@ -167,32 +323,220 @@ impl Bot {
         }
     }
 
-    async fn handle_listen(listen: Arc<ListenInner>, cache_event: Arc<dyn Event + 'static>) {
-        (*listen.handler)(cache_event).await;
+    async fn handle_listen(
+        listen: Arc<ListenInner>,
+        cache_event: Arc<dyn Event + 'static>,
+        matched: Matched,
+    ) -> Option<String> {
+        (*listen.handler)(cache_event, matched).await
     }
 }
 
+/// 没有群与用户的事件不受黑名单限制
 #[cfg(feature = "plugin-access-control")]
-fn is_access(plugin: &Plugin, event: &MsgEvent) -> bool {
-    if !plugin.access_control {
-        return true;
+fn is_blacklisted(blacklist: &[AccessRule], event: &dyn Event, now: i64) -> bool {
+    let (group_id, user_id) = (event.event_group_id(), event.event_user_id());
+    if group_id.is_none() && user_id.is_none() {
+        return false;
+    }
+    blacklist
+        .iter()
+        .any(|rule| rule.matches(group_id, user_id, now))
+}
+
+#[cfg(feature = "plugin-access-control")]
+fn is_access(plugin: &Plugin, event: &dyn Event, now: i64) -> bool {
+    !plugin.access_control
+        || plugin.access_list.allows(
+            plugin.list_mode,
+            event.event_group_id(),
+            event.event_user_id(),
+            now,
+        )
+}
+
+#[cfg(feature = "plugin-access-control")]
+#[test]
+fn access_list_applies_to_all_events() {
+    struct Poke(Option<i64>, Option<i64>);
+    impl Event for Poke {
+        fn de(
+            _: &InternalEvent,
+            _: &BotInformation,
+            _: &mpsc::Sender<ApiAndOneshot>,
+        ) -> Option<Self> {
+            None
+        }
+
+        fn event_group_id(&self) -> Option<i64> {
+            self.0
+        }
+
+        fn event_user_id(&self) -> Option<i64> {
+            self.1
+        }
     }
 
-    let access_list = &plugin.access_list;
-    let in_group = event.is_group();
+    let mut plugin = Plugin::new("p", "0.0.1", Arc::new(|| Box::pin(async {})));
+    plugin.access_control = true;
+    plugin.list_mode = AccessControlMode::WhiteList;
+    plugin.access_list.groups.insert(10);
+    plugin.access_list.friends.insert(2);
+
+    assert!(is_access(&plugin, &Poke(Some(10), Some(3)), 0));
+    assert!(!is_access(&plugin, &Poke(Some(20), Some(2)), 0));
+    assert!(is_access(&plugin, &Poke(None, Some(2)), 0));
+    assert!(!is_access(&plugin, &Poke(None, Some(3)), 0));
+    assert!(is_access(&plugin, &Poke(None, None), 0));
+
+    plugin.list_mode = AccessControlMode::BlackList;
+    assert!(!is_access(&plugin, &Poke(Some(10), Some(3)), 0));
+    assert!(is_access(&plugin, &Poke(Some(20), Some(2)), 0));
+
+    // 只在群 20 内对用户 3 生效
+    plugin.list_mode = AccessControlMode::WhiteList;
+    plugin
+        .access_list
+        .rules
+        .push(AccessRule::users_in_group(20, vec![3]).expires_at(100));
+    assert!(is_access(&plugin, &Poke(Some(20), Some(3)), 0));
+    assert!(!is_access(&plugin, &Poke(Some(20), Some(4)), 0));
+    assert!(!is_access(&plugin, &Poke(Some(20), Some(3)), 100));
+
+    let blacklist = [AccessRule::user(3).expires_at(100)];
+    assert!(is_blacklisted(&blacklist, &Poke(Some(10), Some(3)), 0));
+    assert!(!is_blacklisted(&blacklist, &Poke(Some(10), Some(3)), 100));
+    assert!(!is_blacklisted(&blacklist, &Poke(Some(10), Some(4)), 0));
+}
 
-    match (plugin.list_mode, in_group) {
-        (AccessControlMode::WhiteList, true) => access_list
-            .groups
-            .contains(event.group_id.as_ref().expect("unreachable")),
-        (AccessControlMode::WhiteList, false) => {
-            access_list.friends.contains(&event.sender.user_id)
+#[test]
+fn plugin_errors_only_reach_their_own_listeners() {
+    use crate::bot::plugin_builder::ListenOptions;
+    use crate::event::PluginErrorKind;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static PARSED: AtomicUsize = AtomicUsize::new(0);
+    struct Other;
+    impl Event for Other {
+        fn de(
+            _: &InternalEvent,
+            _: &BotInformation,
+            _: &mpsc::Sender<ApiAndOneshot>,
+        ) -> Option<Self> {
+            PARSED.fetch_add(1, Ordering::SeqCst);
+            Some(Other)
         }
-        (AccessControlMode::BlackList, true) => !access_list
-            .groups
-            .contains(event.group_id.as_ref().expect("unreachable")),
-        (AccessControlMode::BlackList, false) => {
-            !access_list.friends.contains(&event.sender.user_id)
+    }
+
+    let conf = KoviConf::new(
+        1,
+        None,
+        Server::new(
+            Host::IpAddr([127, 0, 0, 1].into()),
+            8081,
+            String::new(),
+            false,
+        ),
+        false,
+    );
+    let mut bot = Bot::build(&conf);
+    let (handled_tx, handled_rx) = std::sync::mpsc::channel();
+    let mut plugin = Plugin::new("p", "0.0.1", Arc::new(|| Box::pin(async {})));
+    let listen = Arc::make_mut(&mut plugin.listen);
+    listen.on(ListenOptions::new(), |_: Arc<Other>| async {});
+    listen.on(ListenOptions::new(), move |event: Arc<PluginErrorEvent>| {
+        let handled_tx = handled_tx.clone();
+        async move {
+            let _ = handled_tx.send(event.plugin_name.clone());
+        }
+    });
+    bot.mount_plugin(plugin);
+
+    let (api_tx, _api_rx) = mpsc::channel(1);
+    let _guard = RT.enter();
+    Bot::dispatch_plugin_error(
+        &bot.snapshot.load(),
+        PluginErrorEvent {
+            plugin_name: "q".to_string(),
+            kind: PluginErrorKind::Error,
+            message: "bad".to_string(),
+            backtrace: None,
+            disabled: false,
+        },
+        &api_tx,
+    );
+
+    let name = handled_rx
+        .recv_timeout(Duration::from_secs(5))
+        .expect("plugin error handled");
+    assert_eq!(name, "q");
+    assert_eq!(PARSED.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn matched_listeners_match_once_and_get_the_result() {
+    use crate::bot::plugin_builder::ListenOptions;
+    use crate::event::OneBotEvent;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static MATCHED: AtomicUsize = AtomicUsize::new(0);
+    struct Ping;
+    impl Event for Ping {
+        fn de(
+            _: &InternalEvent,
+            _: &BotInformation,
+            _: &mpsc::Sender<ApiAndOneshot>,
+        ) -> Option<Self> {
+            Some(Ping)
         }
     }
+
+    let conf = KoviConf::new(
+        1,
+        None,
+        Server::new(
+            Host::IpAddr([127, 0, 0, 1].into()),
+            8081,
+            String::new(),
+            false,
+        ),
+        false,
+    );
+    let mut bot = Bot::build(&conf);
+    let (handled_tx, handled_rx) = std::sync::mpsc::channel();
+    let mut plugin = Plugin::new("p", "0.0.1", Arc::new(|| Box::pin(async {})));
+    let listen = Arc::make_mut(&mut plugin.listen);
+    for matches in [true, false] {
+        let handled_tx = handled_tx.clone();
+        listen.on_matched(
+            ListenOptions::new(),
+            move |_: &Ping| {
+                MATCHED.fetch_add(1, Ordering::SeqCst);
+                matches.then_some(matches)
+            },
+            move |_, matched| {
+                let handled_tx = handled_tx.clone();
+                async move {
+                    let _ = handled_tx.send(matched);
+                }
+            },
+        );
+    }
+    bot.mount_plugin(plugin);
+
+    let (api_tx, _api_rx) = mpsc::channel(1);
+    let _guard = RT.enter();
+    Bot::handler_internal_event(
+        bot.snapshot.load(),
+        InternalEvent::OneBotEvent(OneBotEvent::new("{}")),
+        api_tx,
+    );
+
+    let matched = handled_rx
+        .recv_timeout(Duration::from_secs(5))
+        .expect("matched listener handled");
+    assert!(matched);
+    assert!(handled_rx.recv_timeout(Duration::from_millis(100)).is_err());
+    // 每个监听只匹配一次
+    assert_eq!(MATCHED.load(Ordering::SeqCst), 2);
 }
@@ -7,6 +7,22 @@ use serde_json::{Value, json};
 use crate::error::MessageError;
 
 pub mod add;
+pub mod edit;
+pub mod forward;
+pub mod markup;
+pub mod media;
+pub mod pattern;
+pub mod segment;
+pub mod split;
+pub mod template;
+
+pub use forward::{ForwardMessage, ForwardMsgReturn, ForwardNode};
+pub use markup::MarkupOptions;
+pub use media::{MediaBuilder, MediaConfig, MediaEncoding, MediaSource};
+pub use pattern::{Capture, PatternMatch, RegexCapture, SegmentPattern};
+pub use segment::SegmentKind;
+pub use split::SplitConfig;
+pub use template::{MessageTemplate, TemplateContext, TemplateSet};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Segment {
@@ -195,10 +211,8 @@ impl Message {
         for item in self.iter() {
             match item.type_.as_str() {
                 "text" => {
-                    if let Some(text_data) = item.data.get("text") {
-                        if let Some(text_str) = text_data.as_str() {
-                            result.push_str(text_str);
-                        }
+                    if let Some(text_str) = item.data.get("text").and_then(|v| v.as_str()) {
+                        result.push_str(text_str);
                     }
                 }
                 _ => {
@@ -512,7 +526,7 @@ pub(crate) fn cq_to_arr_inner(message: &str) -> Vec<serde_json::Value> {
 #[cfg(feature = "cqstring")]
 pub fn cq_to_arr(message: CQMessage) -> Message {
     let json_arr = cq_to_arr_inner(&message.0);
-    Message::from_vec_segment_value(json_arr).unwrap()
+    Message::from_vec_segment_value(json_arr).expect("cq_to_arr_inner builds valid segments")
 }
 
 #[cfg(feature = "cqstring")]
@@ -521,15 +535,13 @@ fn parse_cq_code(item: &Segment) -> String {
 
     match item.type_.as_str() {
         "text" => {
-            if let Some(text_data) = item.data.get("text") {
-                if let Some(text_str) = text_data.as_str() {
-                    result.push_str(text_str);
-                }
+            if let Some(text_str) = item.data.get("text").and_then(|v| v.as_str()) {
+                result.push_str(text_str);
             }
         }
         _ => {
             let mut params = Vec::new();
-            for (key, value) in item.data.as_object().unwrap().iter() {
+            for (key, value) in item.data.as_object().into_iter().flatten() {
                 if let Some(value_str) = value.as_str() {
                     params.push(format!("{}={}", key, value_str));
                 }
@@ -582,21 +594,21 @@ fn check_msg() {
             },
         ]
     ))
-    .unwrap();
+    .expect("valid json");
     let text_value: Segment = serde_json::from_value(json!({
         "type":"text",
         "data":{
             "text":"Some msg"
         }
     }))
-    .unwrap();
+    .expect("valid json");
     let face_value: Segment = serde_json::from_value(json!({
         "type":"face",
         "data":{
             "id":"0"
         }
     }))
-    .unwrap();
+    .expect("valid json");
     assert_eq!(msg.get("text")[0], text_value);
     assert_eq!(msg.get("face")[0], face_value);
 
@@ -611,7 +623,7 @@ fn check_msg() {
             }
         ]
     ))
-    .unwrap();
+    .expect("valid json");
     assert!(msg1.contains("text"));
     assert!(msg2.contains("text"));
 }
@@ -57,6 +57,24 @@ impl Message {
         self
     }
 
+    /// 消息加上语音
+    pub fn add_record(mut self, file: &str) -> Self {
+        self.0.push(Segment {
+            type_: "record".to_string(),
+            data: json!({ "file": file }),
+        });
+        self
+    }
+
+    /// 消息加上短视频
+    pub fn add_video(mut self, file: &str) -> Self {
+        self.0.push(Segment {
+            type_: "video".to_string(),
+            data: json!({ "file": file }),
+        });
+        self
+    }
+
     /// 消息加上 segment
     pub fn add_segment<T>(mut self, segment: T) -> Self
     where
@@ -116,6 +134,22 @@ impl Message {
         });
     }
 
+    /// 消息加上语音
+    pub fn push_record(&mut self, file: &str) {
+        self.0.push(Segment {
+            type_: "record".to_string(),
+            data: json!({ "file": file }),
+        });
+    }
+
+    /// 消息加上短视频
+    pub fn push_video(&mut self, file: &str) {
+        self.0.push(Segment {
+            type_: "video".to_string(),
+            data: json!({ "file": file }),
+        });
+    }
+
     pub fn push(&mut self, s: Segment) {
         self.0.push(s);
     }
@@ -158,6 +192,18 @@ impl CQMessage {
         self
     }
 
+    /// 消息加上语音
+    pub fn add_record(mut self, file: &str) -> Self {
+        self.0.push_str(&format!("[CQ:record,file={}]", file));
+        self
+    }
+
+    /// 消息加上短视频
+    pub fn add_video(mut self, file: &str) -> Self {
+        self.0.push_str(&format!("[CQ:video,file={}]", file));
+        self
+    }
+
     /// 消息加上 segment
     pub fn add_segment<T>(mut self, segment: T) -> Self
     where
@@ -203,4 +249,14 @@ impl CQMessage {
     pub fn push_image(&mut self, file: &str) {
         self.0.push_str(&format!("[CQ:image,file={}]", file));
     }
+
+    /// 消息加上语音
+    pub fn push_record(&mut self, file: &str) {
+        self.0.push_str(&format!("[CQ:record,file={}]", file));
+    }
+
+    /// 消息加上短视频
+    pub fn push_video(&mut self, file: &str) {
+        self.0.push_str(&format!("[CQ:video,file={}]", file));
+    }
 }
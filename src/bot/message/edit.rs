@@ -0,0 +1,238 @@
+use super::{Message, Segment};
+use serde_json::{Value, json};
+use std::ops::{Bound, RangeBounds};
+
+/// text segment 的文字
+fn text_of(segment: &Segment) -> Option<&str> {
+    if segment.type_ == "text" {
+        segment.data.get("text").and_then(Value::as_str)
+    } else {
+        None
+    }
+}
+
+fn set_text(segment: &mut Segment, text: String) {
+    match segment.data.as_object_mut() {
+        Some(data) => {
+            data.insert("text".to_string(), Value::String(text));
+        }
+        None => segment.data = json!({ "text": text }),
+    }
+}
+
+impl Message {
+    /// segment 的数量
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 合并相邻的文字，并去掉空的文字
+    pub fn normalize(&mut self) {
+        let mut segments: Vec<Segment> = Vec::with_capacity(self.0.len());
+        for segment in std::mem::take(&mut self.0) {
+            let Some(text) = text_of(&segment) else {
+                segments.push(segment);
+                continue;
+            };
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(last) = segments.last_mut()
+                && let Some(last_text) = text_of(last)
+            {
+                let merged = format!("{last_text}{text}");
+                set_text(last, merged);
+            } else {
+                segments.push(segment);
+            }
+        }
+        self.0 = segments;
+    }
+
+    /// 去掉消息首尾的空白，遇到其他 segment 时停止
+    pub fn trim(&mut self) {
+        self.normalize();
+        if let Some(first) = self.0.first_mut()
+            && let Some(text) = text_of(first)
+        {
+            let trimmed = text.trim_start().to_string();
+            set_text(first, trimmed);
+        }
+        if let Some(last) = self.0.last_mut()
+            && let Some(text) = text_of(last)
+        {
+            let trimmed = text.trim_end().to_string();
+            set_text(last, trimmed);
+        }
+        self.normalize();
+    }
+
+    /// 替换文字，会先合并相邻的文字，被其他 segment 隔开的内容不会被替换
+    pub fn replace_text(&mut self, from: &str, to: &str) {
+        if from.is_empty() {
+            return;
+        }
+        self.normalize();
+        for segment in self.0.iter_mut() {
+            if let Some(text) = text_of(segment)
+                && text.contains(from)
+            {
+                let replaced = text.replace(from, to);
+                set_text(segment, replaced);
+            }
+        }
+        self.normalize();
+    }
+
+    /// 删除此类型的所有 segment
+    pub fn remove_segments(&mut self, type_: &str) {
+        self.0.retain(|segment| segment.type_ != type_);
+    }
+
+    /// 只保留满足条件的 segment
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&Segment) -> bool,
+    {
+        self.0.retain(f);
+    }
+
+    /// 文字的字符数
+    pub fn text_len(&self) -> usize {
+        self.iter()
+            .filter_map(text_of)
+            .map(|text| text.chars().count())
+            .sum()
+    }
+
+    /// 按文字的字符位置截取
+    ///
+    /// 其他 segment 的位置为它前面文字的字符数，位置在范围内的会被保留。
+    ///
+    /// # Examples
+    /// ```
+    /// use kovi::Message;
+    ///
+    /// let msg = Message::new().add_text("ab").add_face(1).add_text("cd");
+    /// assert_eq!(msg.slice(1..3), Message::new().add_text("b").add_face(1).add_text("c"));
+    /// ```
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Message {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.saturating_add(1),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => usize::MAX,
+        };
+
+        let mut result = Message::new();
+        let mut pos = 0;
+        for segment in self.iter() {
+            let Some(text) = text_of(segment) else {
+                if start <= pos && pos < end {
+                    result.push(segment.clone());
+                }
+                continue;
+            };
+            let len = text.chars().count();
+            let from = start.clamp(pos, pos + len) - pos;
+            let to = end.clamp(pos, pos + len) - pos;
+            if from < to {
+                let part: String = text.chars().skip(from).take(to - from).collect();
+                let mut segment = segment.clone();
+                set_text(&mut segment, part);
+                result.push(segment);
+            }
+            pos += len;
+        }
+        result
+    }
+
+    /// 消息中的文字，忽略其他 segment
+    pub fn plain_text(&self) -> String {
+        self.plain_text_with(|_| None)
+    }
+
+    /// 消息中的文字，其他 segment 由 `render` 转换，返回 `None` 时忽略
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use kovi::Message;
+    ///
+    /// fn preview(msg: &Message) -> String {
+    ///     msg.plain_text_with(|segment| match segment.type_.as_str() {
+    ///         "image" => Some("[图片]".to_string()),
+    ///         _ => None,
+    ///     })
+    /// }
+    /// ```
+    pub fn plain_text_with<F>(&self, mut render: F) -> String
+    where
+        F: FnMut(&Segment) -> Option<String>,
+    {
+        let mut result = String::new();
+        for segment in self.iter() {
+            match text_of(segment) {
+                Some(text) => result.push_str(text),
+                None => {
+                    if let Some(text) = render(segment) {
+                        result.push_str(&text);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[test]
+fn edit_messages() {
+    let mut msg = Message::new()
+        .add_text("  he")
+        .add_text("llo ")
+        .add_text("")
+        .add_face(1)
+        .add_text(" wor")
+        .add_text("ld  ");
+    msg.trim();
+    assert_eq!(
+        msg,
+        Message::new()
+            .add_text("hello ")
+            .add_face(1)
+            .add_text(" world")
+    );
+    assert_eq!(msg.text_len(), 12);
+
+    msg.replace_text("o", "0");
+    assert_eq!(msg.plain_text(), "hell0  w0rld");
+    assert_eq!(
+        msg.plain_text_with(|segment| Some(format!("<{}>", segment.type_))),
+        "hell0 <face> w0rld"
+    );
+
+    assert_eq!(
+        msg.slice(4..8),
+        Message::new().add_text("0 ").add_face(1).add_text(" w")
+    );
+    assert_eq!(msg.slice(..6).len(), 1);
+    assert_eq!(msg.slice(6..).len(), 2);
+    assert_eq!(msg.slice(6..=6), Message::new().add_face(1).add_text(" "));
+    assert_eq!(msg.slice(..=usize::MAX), msg);
+    assert!(
+        msg.slice((Bound::Excluded(usize::MAX), Bound::Unbounded))
+            .is_empty()
+    );
+
+    msg.remove_segments("face");
+    assert_eq!(msg.len(), 2);
+    msg.normalize();
+    assert_eq!(msg, Message::from("hell0  w0rld"));
+}
@@ -0,0 +1,250 @@
+use super::segment::{Node, SegmentKind};
+use super::{Message, Segment};
+use serde_json::Value;
+
+/// 合并转发的一个节点
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForwardNode {
+    /// 自定义的节点
+    Custom {
+        /// 显示的昵称
+        name: String,
+        /// 显示的 QQ 号
+        uin: i64,
+        content: Message,
+    },
+    /// 引用一条已有的消息
+    Reference { message_id: i32 },
+}
+
+/// 合并转发消息，通过 `RuntimeBot::send_group_forward_msg()` 等发送
+///
+/// # Examples
+/// ```no_run
+/// use kovi::bot::message::ForwardMessage;
+/// use kovi::{ApiReturn, MsgEvent, RuntimeBot};
+///
+/// async fn forward(bot: &RuntimeBot, event: &MsgEvent, group_id: i64) -> Result<(), ApiReturn> {
+///     let forward = ForwardMessage::new()
+///         .add_custom("Kovi", 10001, "你好")
+///         .add_reference(event.message_id);
+///     bot.send_group_forward_msg(group_id, forward).await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ForwardMessage(Vec<ForwardNode>);
+
+impl ForwardMessage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 加上自定义的节点
+    pub fn add_custom<T>(mut self, name: &str, uin: i64, content: T) -> Self
+    where
+        Message: From<T>,
+    {
+        self.push_custom(name, uin, content);
+        self
+    }
+
+    /// 加上引用已有消息的节点
+    pub fn add_reference(mut self, message_id: i32) -> Self {
+        self.push_reference(message_id);
+        self
+    }
+
+    /// 加上自定义的节点
+    pub fn push_custom<T>(&mut self, name: &str, uin: i64, content: T)
+    where
+        Message: From<T>,
+    {
+        self.0.push(ForwardNode::Custom {
+            name: name.to_string(),
+            uin,
+            content: Message::from(content),
+        });
+    }
+
+    /// 加上引用已有消息的节点
+    pub fn push_reference(&mut self, message_id: i32) {
+        self.0.push(ForwardNode::Reference { message_id });
+    }
+
+    pub fn push(&mut self, node: ForwardNode) {
+        self.0.push(node);
+    }
+
+    pub fn nodes(&self) -> &[ForwardNode] {
+        &self.0
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, ForwardNode> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 从 node segment 中解析，跳过其他 segment
+    pub fn from_message(msg: &Message) -> Self {
+        ForwardMessage(
+            msg.iter()
+                .filter(|segment| segment.type_ == "node")
+                .filter_map(|segment| node_from_value(&segment.data))
+                .collect(),
+        )
+    }
+
+    /// 解析 `get_forward_msg` 返回的 `data`
+    ///
+    /// 兼容 OneBot 标准的 node segment 数组，以及部分实现返回的带 `sender` 的消息数组。
+    pub fn from_forward_data(data: &Value) -> Option<Self> {
+        let nodes = data
+            .get("messages")
+            .or_else(|| data.get("message"))?
+            .as_array()?;
+        Some(ForwardMessage(
+            nodes
+                .iter()
+                .filter_map(|node| match node.get("type").and_then(Value::as_str) {
+                    Some("node") => node.get("data").and_then(node_from_value),
+                    _ => node_from_value(node),
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl From<ForwardNode> for Segment {
+    fn from(node: ForwardNode) -> Self {
+        let node = match node {
+            ForwardNode::Custom { name, uin, content } => Node {
+                user_id: Some(uin.to_string()),
+                nickname: Some(name),
+                content: Some(content),
+                ..Default::default()
+            },
+            ForwardNode::Reference { message_id } => Node {
+                id: Some(message_id.to_string()),
+                ..Default::default()
+            },
+        };
+        Segment::from(SegmentKind::Node(node))
+    }
+}
+
+impl From<ForwardMessage> for Message {
+    fn from(forward: ForwardMessage) -> Self {
+        Message::from(forward.0.into_iter().map(Segment::from).collect::<Vec<_>>())
+    }
+}
+
+impl IntoIterator for ForwardMessage {
+    type Item = ForwardNode;
+    type IntoIter = std::vec::IntoIter<ForwardNode>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// 发送合并转发的返回值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardMsgReturn {
+    pub message_id: i32,
+    /// 合并转发 ID，可用于 `get_forward_msg`，部分实现不返回
+    pub forward_id: Option<String>,
+}
+
+impl ForwardMsgReturn {
+    pub(crate) fn from_data(data: &Value) -> Option<Self> {
+        let message_id = data.get("message_id").and_then(Value::as_i64)? as i32;
+        let forward_id = data
+            .get("forward_id")
+            .or_else(|| data.get("res_id"))
+            .and_then(value_to_string);
+        Some(ForwardMsgReturn {
+            message_id,
+            forward_id,
+        })
+    }
+}
+
+/// 解析 node segment 的 data，或带 `sender` 的消息
+fn node_from_value(data: &Value) -> Option<ForwardNode> {
+    let Some(content) = data.get("content").or_else(|| data.get("message")) else {
+        let message_id = data.get("id").and_then(value_to_string)?.parse().ok()?;
+        return Some(ForwardNode::Reference { message_id });
+    };
+
+    let sender = data.get("sender");
+    let field = |key: &str| {
+        data.get(key)
+            .or_else(|| sender.and_then(|sender| sender.get(key)))
+            .and_then(value_to_string)
+    };
+    let name = field("nickname")
+        .or_else(|| field("name"))
+        .unwrap_or_default();
+    let uin = field("user_id")
+        .or_else(|| field("uin"))
+        .and_then(|uin| uin.parse().ok())
+        .unwrap_or_default();
+    let content = match content {
+        Value::String(text) => Message::from(text.as_str()),
+        content => Message::from_value(content.clone()).ok()?,
+    };
+    Some(ForwardNode::Custom { name, uin, content })
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[test]
+fn forward_message_round_trip() {
+    use serde_json::json;
+
+    let forward = ForwardMessage::new()
+        .add_custom("Kovi", 10001, Message::new().add_text("hi").add_face(178))
+        .add_reference(123);
+    let msg = Message::from(forward.clone());
+    assert_eq!(
+        msg.get_from_index(1),
+        Some(&Segment::new("node", json!({"id": "123"})))
+    );
+    assert_eq!(ForwardMessage::from_message(&msg), forward);
+
+    let data = json!({ "message": serde_json::to_value(&msg).expect("message serializes") });
+    assert_eq!(ForwardMessage::from_forward_data(&data), Some(forward));
+
+    // 部分实现返回的是带 sender 的消息
+    let data = json!({"messages": [{
+        "sender": {"user_id": 10001, "nickname": "Kovi"},
+        "time": 0,
+        "content": "hi",
+    }]});
+    assert_eq!(
+        ForwardMessage::from_forward_data(&data),
+        Some(ForwardMessage::new().add_custom("Kovi", 10001, "hi"))
+    );
+
+    assert_eq!(
+        ForwardMsgReturn::from_data(&json!({"message_id": 1, "res_id": "abc"})),
+        Some(ForwardMsgReturn {
+            message_id: 1,
+            forward_id: Some("abc".to_string()),
+        })
+    );
+}
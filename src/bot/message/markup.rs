@@ -0,0 +1,292 @@
+use super::segment::SegmentKind;
+use super::{MediaBuilder, MediaConfig, MediaSource, Message, Segment};
+use crate::error::MessageError;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::CharIndices;
+
+impl Message {
+    /// 从标记文本生成消息
+    ///
+    /// 标记文本的格式：
+    ///
+    /// - `@{123456}` at 某人，`@{all}` at 全体成员
+    /// - `{face:14}` 表情
+    /// - `{image:...}`、`{record:...}`、`{video:...}` 图片、语音与短视频，
+    ///   只接受含有 `://` 的 URL，原样发送。本地文件需要 `MarkupOptions::allow_local_files()`
+    /// - `{reply:123}` 引用消息
+    /// - `\{`、`\}`、`\\` 转义，其余字符都是文字
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use kovi::{Message, MessageError};
+    ///
+    /// fn hello() -> Result<Message, MessageError> {
+    ///     Message::from_markup("Hello @{123456}, {image:https://example.com/a.png} {face:14}")
+    /// }
+    /// ```
+    pub fn from_markup(markup: &str) -> Result<Message, MessageError> {
+        parse(markup, None)
+    }
+
+    /// 按 `options` 从标记文本生成消息，见 `Message::from_markup()`
+    ///
+    /// 允许本地文件时，文件按 `options` 中的 `MediaConfig` 编码。
+    ///
+    /// ```no_run
+    /// use kovi::bot::message::MarkupOptions;
+    /// use kovi::{Message, RuntimeBot};
+    ///
+    /// async fn local_image(bot: &RuntimeBot) -> Result<Message, Box<dyn std::error::Error>> {
+    ///     let options = MarkupOptions::new()
+    ///         .allow_local_files(true)
+    ///         .media_config(bot.get_media_config()?);
+    ///     let msg = Message::from_markup_with("{image:/tmp/a.png}", &options).await?;
+    ///     Ok(msg)
+    /// }
+    /// ```
+    pub async fn from_markup_with(
+        markup: &str,
+        options: &MarkupOptions,
+    ) -> Result<Message, MessageError> {
+        let mut local_files = Vec::new();
+        let mut msg = parse(
+            markup,
+            options.allow_local_files.then_some(&mut local_files),
+        )?;
+        for (index, media) in local_files {
+            msg.0[index] = media.build(&options.media).await?;
+        }
+        Ok(msg)
+    }
+
+    /// 转换为标记文本，用于日志
+    ///
+    /// Base64 编码的文件会被省略，其他无法用标记表示的 segment 显示为 `{类型}`，
+    /// 这两种情况无法再转换回消息。
+    pub fn to_markup(&self) -> String {
+        let mut result = String::new();
+        for kind in self.kinds() {
+            match kind {
+                SegmentKind::Text(text) => result.push_str(&escape_text(&text.text)),
+                SegmentKind::At(at) => result.push_str(&format!("@{{{}}}", escape(&at.qq))),
+                SegmentKind::Face(face) => {
+                    result.push_str(&format!("{{face:{}}}", escape(&face.id)))
+                }
+                SegmentKind::Reply(reply) => {
+                    result.push_str(&format!("{{reply:{}}}", escape(&reply.id)))
+                }
+                SegmentKind::Image(media) => push_media(&mut result, "image", &media.file),
+                SegmentKind::Record(media) => push_media(&mut result, "record", &media.file),
+                SegmentKind::Video(media) => push_media(&mut result, "video", &media.file),
+                kind => result.push_str(&format!("{{{}}}", Segment::from(kind).type_)),
+            }
+        }
+        result
+    }
+}
+
+/// `Message::from_markup_with()` 的选项
+#[derive(Debug, Clone, Default)]
+pub struct MarkupOptions {
+    allow_local_files: bool,
+    media: MediaConfig,
+}
+
+impl MarkupOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 允许 `{image:/path}` 等发送本地文件，默认只接受 URL
+    ///
+    /// 只应对可信的标记文本开启，否则发送标记的人可以让 Bot 读取并发出它所在机器上的任意文件。
+    pub fn allow_local_files(mut self, allow: bool) -> Self {
+        self.allow_local_files = allow;
+        self
+    }
+
+    /// 本地文件的编码方式，见 `RuntimeBot::get_media_config()`
+    pub fn media_config(mut self, config: MediaConfig) -> Self {
+        self.media = config;
+        self
+    }
+}
+
+/// 本地文件先用空的 segment 占位，连同位置记录到 `local_files`，为 `None` 时不允许本地文件
+fn parse(
+    markup: &str,
+    mut local_files: Option<&mut Vec<(usize, MediaBuilder)>>,
+) -> Result<Message, MessageError> {
+    let mut msg = Message::new();
+    let mut text = String::new();
+    let mut chars = markup.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => text.push(chars.next().map_or('\\', |(_, c)| c)),
+            '@' if chars.peek().is_some_and(|(_, c)| *c == '{') => {
+                chars.next();
+                let body = read_tag(&mut chars, i)?;
+                flush_text(&mut msg, &mut text);
+                push_at(&mut msg, &body, i)?;
+            }
+            '{' => {
+                let body = read_tag(&mut chars, i)?;
+                flush_text(&mut msg, &mut text);
+                push_tag(&mut msg, &body, i, local_files.as_deref_mut())?;
+            }
+            '}' => return Err(parse_error(i, "unmatched '}'")),
+            c => text.push(c),
+        }
+    }
+    flush_text(&mut msg, &mut text);
+    Ok(msg)
+}
+
+/// 读取到匹配的 `}`，返回去掉转义后的内容
+fn read_tag(chars: &mut Peekable<CharIndices>, start: usize) -> Result<String, MessageError> {
+    let mut body = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '\\' => body.push(chars.next().map_or('\\', |(_, c)| c)),
+            '}' => return Ok(body),
+            c => body.push(c),
+        }
+    }
+    Err(parse_error(start, "unclosed '{'"))
+}
+
+fn flush_text(msg: &mut Message, text: &mut String) {
+    if !text.is_empty() {
+        msg.push_text(std::mem::take(text));
+    }
+}
+
+fn push_at(msg: &mut Message, qq: &str, at: usize) -> Result<(), MessageError> {
+    let qq = qq.trim();
+    if qq != "all" && qq.parse::<i64>().is_err() {
+        return Err(parse_error(at, &format!("invalid at target '{qq}'")));
+    }
+    msg.push_at(qq);
+    Ok(())
+}
+
+fn push_tag(
+    msg: &mut Message,
+    body: &str,
+    at: usize,
+    local_files: Option<&mut Vec<(usize, MediaBuilder)>>,
+) -> Result<(), MessageError> {
+    let Some((name, value)) = body.split_once(':') else {
+        return Err(parse_error(at, &format!("missing value in '{{{body}}}'")));
+    };
+    let number = |value: &str| {
+        value
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| parse_error(at, &format!("invalid {name} id '{value}'")))
+    };
+
+    match name.trim() {
+        "at" => push_at(msg, value, at)?,
+        "face" => msg.push_face(number(value)?),
+        "reply" => msg.push(Segment::new(
+            "reply",
+            serde_json::json!({ "id": number(value)?.to_string() }),
+        )),
+        "image" => push_media_tag(msg, MediaBuilder::image, value, at, local_files)?,
+        "record" => push_media_tag(msg, MediaBuilder::record, value, at, local_files)?,
+        "video" => push_media_tag(msg, MediaBuilder::video, value, at, local_files)?,
+        name => return Err(parse_error(at, &format!("unknown tag '{name}'"))),
+    }
+    Ok(())
+}
+
+fn push_media_tag(
+    msg: &mut Message,
+    media: fn(MediaSource) -> MediaBuilder,
+    value: &str,
+    at: usize,
+    local_files: Option<&mut Vec<(usize, MediaBuilder)>>,
+) -> Result<(), MessageError> {
+    if value.contains("://") {
+        msg.push(media(MediaSource::url(value)).segment(value.to_string()));
+        return Ok(());
+    }
+    let Some(local_files) = local_files else {
+        return Err(parse_error(
+            at,
+            &format!("local file '{value}' is not allowed, see MarkupOptions::allow_local_files"),
+        ));
+    };
+    local_files.push((msg.0.len(), media(MediaSource::from(Path::new(value)))));
+    msg.push(Segment::new("text", serde_json::json!({ "text": "" })));
+    Ok(())
+}
+
+fn push_media(result: &mut String, type_: &str, file: &str) {
+    if file.starts_with("base64://") {
+        result.push_str(&format!("{{{type_}:base64://...}}"));
+    } else {
+        result.push_str(&format!("{{{type_}:{}}}", escape(file)));
+    }
+}
+
+/// 转义文字，使其在标记文本中原样显示
+pub(crate) fn escape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '{' | '}') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('}', "\\}")
+}
+
+fn parse_error(at: usize, reason: &str) -> MessageError {
+    MessageError::ParseError(format!("markup at byte {at}: {reason}"))
+}
+
+#[test]
+fn markup_round_trip() {
+    let markup =
+        r"Hello @{123456}, \{not a tag\} {face:14}{image:https://example.com/a.png?x={1\}}";
+    let msg = Message::from_markup(markup).expect("valid markup");
+    assert_eq!(
+        msg,
+        Message::new()
+            .add_text("Hello ")
+            .add_at("123456")
+            .add_text(", {not a tag} ")
+            .add_face(14)
+            .add_image("https://example.com/a.png?x={1}")
+    );
+    assert_eq!(msg.to_markup(), markup);
+
+    let msg = Message::from_markup("{reply:1}@{all} hi").expect("valid markup");
+    assert_eq!(msg.reply_id(), Some(1));
+    assert!(msg.ats()[0].is_all());
+
+    for bad in ["{face:x}", "@{bob}", "{face:1", "a}", "{gif:a}", "{image}"] {
+        assert!(Message::from_markup(bad).is_err(), "{bad}");
+    }
+    assert!(Message::from_markup("{image:/no/such/file.png}").is_err());
+
+    let path = std::env::temp_dir().join("kovi_markup_local_file.txt");
+    std::fs::write(&path, "hi").expect("temp file writable");
+    let markup = format!("{{image:{}}}", path.display());
+    let err = Message::from_markup(&markup).expect_err("local files need opt-in");
+    assert!(err.to_string().contains("allow_local_files"));
+    let options = MarkupOptions::new().allow_local_files(true);
+    let msg = crate::RT
+        .block_on(Message::from_markup_with(&markup, &options))
+        .expect("local file allowed");
+    assert_eq!(msg.images()[0].file, "base64://aGk=");
+    std::fs::remove_file(&path).expect("temp file removable");
+}
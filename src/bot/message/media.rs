@@ -0,0 +1,300 @@
+use super::Segment;
+use super::segment::{Media, SegmentKind};
+use crate::error::MessageError;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 发送本地文件时的编码方式，位于配置文件的 `[config.media]`
+///
+/// 运行中的配置可以通过 `RuntimeBot::get_media_config()` 获取。
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MediaConfig {
+    /// 允许以 `file://` 发送本地文件，需要 OneBot 服务端能读到 Bot 所在机器的文件
+    #[serde(default)]
+    pub file_uri: bool,
+    /// 允许 `file://` 时，不超过此大小的本地文件仍以 Base64 发送，字节
+    #[serde(default = "default_base64_max_size")]
+    pub base64_max_size: u64,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        MediaConfig {
+            file_uri: false,
+            base64_max_size: default_base64_max_size(),
+        }
+    }
+}
+
+fn default_base64_max_size() -> u64 {
+    1024 * 1024
+}
+
+/// 媒体文件的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaSource {
+    /// 文件内容，总是以 Base64 发送
+    Bytes(Vec<u8>),
+    /// 本地文件，按 `MediaConfig` 选择 Base64 或 `file://`
+    Path(PathBuf),
+    /// 网络 URL，或服务端能识别的其他字符串，原样发送
+    Url(String),
+}
+
+impl MediaSource {
+    pub fn url<T: Into<String>>(url: T) -> Self {
+        MediaSource::Url(url.into())
+    }
+}
+
+impl From<&[u8]> for MediaSource {
+    fn from(bytes: &[u8]) -> Self {
+        MediaSource::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for MediaSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        MediaSource::Bytes(bytes)
+    }
+}
+
+impl From<&Path> for MediaSource {
+    fn from(path: &Path) -> Self {
+        MediaSource::Path(path.to_path_buf())
+    }
+}
+
+impl From<PathBuf> for MediaSource {
+    fn from(path: PathBuf) -> Self {
+        MediaSource::Path(path)
+    }
+}
+
+/// 本地文件的编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MediaEncoding {
+    /// 按 `MediaConfig` 与文件大小选择
+    #[default]
+    Auto,
+    Base64,
+    FileUri,
+}
+
+/// 图片、语音与短视频 segment 的构建器
+///
+/// # Examples
+/// ```no_run
+/// use kovi::bot::message::{MediaBuilder, MediaSource};
+/// use kovi::{Message, PluginBuilder};
+///
+/// async fn media() -> Result<Message, Box<dyn std::error::Error>> {
+///     let config = PluginBuilder::get_runtime_bot().get_media_config()?;
+///     let image = MediaBuilder::image(std::path::Path::new("a.png")).flash();
+///     let record = MediaBuilder::record(MediaSource::url("https://example.com/a.mp3")).cache(false);
+///     let mut msg = Message::new();
+///     msg.push(image.build(&config).await?);
+///     msg.push(record.build(&config).await?);
+///     Ok(msg)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MediaBuilder {
+    type_: &'static str,
+    source: MediaSource,
+    encoding: MediaEncoding,
+    flash: bool,
+    cache: Option<bool>,
+    proxy: Option<bool>,
+    timeout: Option<Duration>,
+}
+
+impl MediaBuilder {
+    fn new(type_: &'static str, source: MediaSource) -> Self {
+        MediaBuilder {
+            type_,
+            source,
+            encoding: MediaEncoding::Auto,
+            flash: false,
+            cache: None,
+            proxy: None,
+            timeout: None,
+        }
+    }
+
+    pub fn image<T: Into<MediaSource>>(source: T) -> Self {
+        Self::new("image", source.into())
+    }
+
+    pub fn record<T: Into<MediaSource>>(source: T) -> Self {
+        Self::new("record", source.into())
+    }
+
+    pub fn video<T: Into<MediaSource>>(source: T) -> Self {
+        Self::new("video", source.into())
+    }
+
+    /// 本地文件的编码方式，不影响字节与 URL
+    pub fn encoding(mut self, encoding: MediaEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// 以闪照发送，只对图片有效
+    pub fn flash(mut self) -> Self {
+        self.flash = true;
+        self
+    }
+
+    /// 发送网络文件时是否使用已缓存的文件
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// 发送网络文件时是否通过代理下载
+    pub fn proxy(mut self, proxy: bool) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// 发送网络文件时下载的超时时间
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 生成 segment，读取本地文件失败时返回错误
+    ///
+    /// `config` 用于 `MediaEncoding::Auto`，读取文件与 Base64 编码在阻塞线程池中进行。
+    pub async fn build(self, config: &MediaConfig) -> Result<Segment, MessageError> {
+        if let MediaSource::Url(url) = &self.source {
+            let url = url.clone();
+            return Ok(self.segment(url));
+        }
+        let config = config.clone();
+        tokio::task::spawn_blocking(move || {
+            let file = file_of(&self.source, self.encoding, &config)?;
+            Ok(self.segment(file))
+        })
+        .await
+        .map_err(|e| MessageError::FileReadError(e.to_string()))?
+    }
+
+    /// 用已经得到的 `file` 生成 segment
+    pub(crate) fn segment(self, file: String) -> Segment {
+        let flag = |b: bool| if b { "1" } else { "0" }.to_string();
+        let media = Media {
+            file,
+            type_: (self.flash && self.type_ == "image").then(|| "flash".to_string()),
+            cache: self.cache.map(flag),
+            proxy: self.proxy.map(flag),
+            timeout: self.timeout.map(|t| t.as_secs().to_string()),
+            ..Default::default()
+        };
+        let kind = match self.type_ {
+            "image" => SegmentKind::Image(media),
+            "record" => SegmentKind::Record(media),
+            _ => SegmentKind::Video(media),
+        };
+        Segment::from(kind)
+    }
+}
+
+fn file_of(
+    source: &MediaSource,
+    encoding: MediaEncoding,
+    config: &MediaConfig,
+) -> Result<String, MessageError> {
+    let path = match source {
+        MediaSource::Bytes(bytes) => return Ok(to_base64(bytes)),
+        MediaSource::Url(url) => return Ok(url.clone()),
+        MediaSource::Path(path) => path,
+    };
+
+    let use_file_uri = match encoding {
+        MediaEncoding::Base64 => false,
+        MediaEncoding::FileUri => true,
+        MediaEncoding::Auto => {
+            config.file_uri
+                && std::fs::metadata(path)
+                    .map_err(|e| read_error(path, e))?
+                    .len()
+                    > config.base64_max_size
+        }
+    };
+
+    if use_file_uri {
+        let path = std::path::absolute(path).map_err(|e| read_error(path, e))?;
+        let path = path.to_string_lossy().replace('\\', "/");
+        Ok(format!("file:///{}", path.trim_start_matches('/')))
+    } else {
+        let bytes = std::fs::read(path).map_err(|e| read_error(path, e))?;
+        Ok(to_base64(&bytes))
+    }
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    format!("base64://{}", STANDARD.encode(bytes))
+}
+
+fn read_error(path: &Path, e: std::io::Error) -> MessageError {
+    MessageError::FileReadError(format!("{}: {e}", path.display()))
+}
+
+#[test]
+fn build_media_segments() {
+    use serde_json::json;
+
+    let config = MediaConfig::default();
+    let build = |media: MediaBuilder| crate::RT.block_on(media.build(&config));
+
+    let image = build(MediaBuilder::image(&b"hi"[..]).flash()).expect("media builds");
+    assert_eq!(
+        image,
+        Segment::new("image", json!({"file": "base64://aGk=", "type": "flash"}))
+    );
+
+    let record = build(
+        MediaBuilder::record(MediaSource::url("https://example.com/a.mp3"))
+            .flash()
+            .cache(false)
+            .proxy(true),
+    )
+    .expect("media builds");
+    assert_eq!(
+        record,
+        Segment::new(
+            "record",
+            json!({"file": "https://example.com/a.mp3", "cache": "0", "proxy": "1"})
+        )
+    );
+
+    let path = std::env::temp_dir().join("kovi_build_media_segments.txt");
+    std::fs::write(&path, b"hi").expect("write temp file");
+    let video = build(MediaBuilder::video(path.as_path())).expect("media builds");
+    assert_eq!(video.data["file"], "base64://aGk=");
+    let video = build(MediaBuilder::video(path.as_path()).encoding(MediaEncoding::FileUri))
+        .expect("media builds");
+    let file = video.data["file"].as_str().expect("file is a string");
+    assert!(file.starts_with("file:///") && file.ends_with("kovi_build_media_segments.txt"));
+    let config = MediaConfig {
+        file_uri: true,
+        base64_max_size: 1,
+    };
+    let video = crate::RT
+        .block_on(MediaBuilder::video(path.as_path()).build(&config))
+        .expect("media builds");
+    assert!(
+        video.data["file"]
+            .as_str()
+            .expect("file is a string")
+            .starts_with("file:///")
+    );
+    std::fs::remove_file(&path).expect("remove temp file");
+
+    assert!(build(MediaBuilder::image(PathBuf::from("/no/such/file"))).is_err());
+}
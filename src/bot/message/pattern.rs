@@ -0,0 +1,355 @@
+use super::Message;
+use super::segment::{At, Media, Reply, SegmentKind};
+use crate::bot::event::event_message;
+use crate::bot::plugin_builder::event::Event;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// 按 segment 匹配消息的模式
+///
+/// 匹配前会合并相邻的文字并去掉首尾空白，只有空白的文字会被忽略。
+/// 模式需要与整条消息匹配，每个元素对应一个 segment。
+///
+/// # Examples
+/// ```no_run
+/// use kovi::MsgEvent;
+/// use kovi::bot::message::SegmentPattern;
+/// use kovi::regex::Regex;
+///
+/// async fn ocr(event: &MsgEvent) -> Result<(), kovi::regex::Error> {
+///     // 回复一条消息并说 “quote”，回复时自动加上的 at 可有可无
+///     let quote = SegmentPattern::new()
+///         .reply()
+///         .optional(SegmentPattern::new().at_self())
+///         .text("quote");
+///
+///     // 一张图片后跟着 “ocr”
+///     let pattern = SegmentPattern::new().image().regex(Regex::new(r"^ocr\b")?);
+///
+///     if let Some(captures) = pattern.match_event(event) {
+///         let image = captures.images()[0];
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SegmentPattern {
+    elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone)]
+enum Element {
+    Text(String),
+    AnyText,
+    Regex(Regex),
+    At,
+    AtSelf,
+    Image,
+    Reply,
+    Optional(Vec<Element>),
+}
+
+/// 模式中一个元素匹配到的内容
+#[derive(Debug, Clone, PartialEq)]
+pub enum Capture {
+    Text(String),
+    Regex(RegexCapture),
+    At(At),
+    Image(Media),
+    Reply(Reply),
+}
+
+/// 正则匹配到的分组
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RegexCapture {
+    /// 按序号的分组，第 0 个为整个匹配，未参与匹配的分组为 `None`
+    pub groups: Vec<Option<String>>,
+    /// 有名字的分组
+    pub named: HashMap<String, String>,
+}
+
+impl RegexCapture {
+    pub(crate) fn new(regex: &Regex, text: &str) -> Option<RegexCapture> {
+        let captures = regex.captures(text)?;
+        let groups = captures
+            .iter()
+            .map(|group| group.map(|m| m.as_str().to_string()))
+            .collect();
+        let named = regex
+            .capture_names()
+            .flatten()
+            .filter_map(|name| Some((name.to_string(), captures.name(name)?.as_str().to_string())))
+            .collect();
+        Some(RegexCapture { groups, named })
+    }
+
+    /// 第 `index` 个分组，第 0 个为整个匹配
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.groups.get(index)?.as_deref()
+    }
+
+    /// 名为 `name` 的分组
+    pub fn name(&self, name: &str) -> Option<&str> {
+        self.named.get(name).map(String::as_str)
+    }
+}
+
+/// 模式匹配的结果
+///
+/// 每个元素按顺序对应一项，没有匹配到的可选元素为 `None`。
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PatternMatch {
+    pub captures: Vec<Option<Capture>>,
+}
+
+impl PatternMatch {
+    /// 第 `index` 个元素匹配到的内容
+    pub fn get(&self, index: usize) -> Option<&Capture> {
+        self.captures.get(index)?.as_ref()
+    }
+
+    /// 所有匹配到的内容
+    pub fn iter(&self) -> impl Iterator<Item = &Capture> {
+        self.captures.iter().flatten()
+    }
+
+    /// 所有匹配到的文字，包括正则匹配的文字
+    pub fn texts(&self) -> Vec<&str> {
+        self.iter()
+            .filter_map(|capture| match capture {
+                Capture::Text(text) => Some(text.as_str()),
+                Capture::Regex(regex) => regex.get(0),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 第一个正则匹配到的分组
+    pub fn regex(&self) -> Option<&RegexCapture> {
+        self.iter().find_map(|capture| match capture {
+            Capture::Regex(regex) => Some(regex),
+            _ => None,
+        })
+    }
+
+    pub fn images(&self) -> Vec<&Media> {
+        self.iter()
+            .filter_map(|capture| match capture {
+                Capture::Image(media) => Some(media),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn ats(&self) -> Vec<&At> {
+        self.iter()
+            .filter_map(|capture| match capture {
+                Capture::At(at) => Some(at),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 引用的消息 ID
+    pub fn reply_id(&self) -> Option<i32> {
+        self.iter().find_map(|capture| match capture {
+            Capture::Reply(reply) => reply.id.parse().ok(),
+            _ => None,
+        })
+    }
+}
+
+impl SegmentPattern {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 内容与 `text` 相同的文字
+    pub fn text<S: Into<String>>(mut self, text: S) -> Self {
+        self.elements.push(Element::Text(text.into()));
+        self
+    }
+
+    /// 任意文字
+    pub fn any_text(mut self) -> Self {
+        self.elements.push(Element::AnyText);
+        self
+    }
+
+    /// 能被 `regex` 匹配的文字，不会自动加上 `^` 与 `$`
+    pub fn regex(mut self, regex: Regex) -> Self {
+        self.elements.push(Element::Regex(regex));
+        self
+    }
+
+    /// at 任何人，包括全体成员
+    pub fn at(mut self) -> Self {
+        self.elements.push(Element::At);
+        self
+    }
+
+    /// at Bot 自己
+    pub fn at_self(mut self) -> Self {
+        self.elements.push(Element::AtSelf);
+        self
+    }
+
+    pub fn image(mut self) -> Self {
+        self.elements.push(Element::Image);
+        self
+    }
+
+    /// 引用回复
+    pub fn reply(mut self) -> Self {
+        self.elements.push(Element::Reply);
+        self
+    }
+
+    /// 可有可无的一段模式
+    pub fn optional(mut self, pattern: SegmentPattern) -> Self {
+        self.elements.push(Element::Optional(pattern.elements));
+        self
+    }
+
+    /// 匹配消息，`self_id` 用于 `at_self()`
+    pub fn matches(&self, msg: &Message, self_id: i64) -> Option<PatternMatch> {
+        let mut msg = msg.clone();
+        msg.normalize();
+        let tokens: Vec<SegmentKind> = msg
+            .kinds()
+            .filter_map(|kind| match kind {
+                SegmentKind::Text(mut text) => {
+                    text.text = text.text.trim().to_string();
+                    (!text.text.is_empty()).then_some(SegmentKind::Text(text))
+                }
+                kind => Some(kind),
+            })
+            .collect();
+
+        let mut captures = Vec::new();
+        match_seq(&self.elements, None, &tokens, self_id, &mut captures)
+            .then_some(PatternMatch { captures })
+    }
+
+    /// 匹配消息事件，不是消息事件时返回 `None`
+    pub fn match_event(&self, event: &dyn Event) -> Option<PatternMatch> {
+        let (msg, self_id) = event_message(event)?;
+        self.matches(msg, self_id)
+    }
+}
+
+/// 匹配完当前的元素后还要继续匹配的元素
+struct Rest<'a> {
+    elements: &'a [Element],
+    next: Option<&'a Rest<'a>>,
+}
+
+fn match_seq(
+    elements: &[Element],
+    rest: Option<&Rest>,
+    tokens: &[SegmentKind],
+    self_id: i64,
+    captures: &mut Vec<Option<Capture>>,
+) -> bool {
+    let Some((first, elements)) = elements.split_first() else {
+        return match rest {
+            Some(rest) => match_seq(rest.elements, rest.next, tokens, self_id, captures),
+            None => tokens.is_empty(),
+        };
+    };
+
+    let len = captures.len();
+    if let Element::Optional(inner) = first {
+        let after = Rest {
+            elements,
+            next: rest,
+        };
+        if match_seq(inner, Some(&after), tokens, self_id, captures) {
+            return true;
+        }
+        captures.truncate(len);
+        captures.resize(len + count_captures(inner), None);
+        if match_seq(elements, rest, tokens, self_id, captures) {
+            return true;
+        }
+    } else if let Some((token, tokens)) = tokens.split_first()
+        && let Some(capture) = capture(first, token, self_id)
+    {
+        captures.push(Some(capture));
+        if match_seq(elements, rest, tokens, self_id, captures) {
+            return true;
+        }
+    }
+    captures.truncate(len);
+    false
+}
+
+fn count_captures(elements: &[Element]) -> usize {
+    elements
+        .iter()
+        .map(|element| match element {
+            Element::Optional(inner) => count_captures(inner),
+            _ => 1,
+        })
+        .sum()
+}
+
+fn capture(element: &Element, token: &SegmentKind, self_id: i64) -> Option<Capture> {
+    match (element, token) {
+        (Element::Text(expected), SegmentKind::Text(text)) if text.text == *expected => {
+            Some(Capture::Text(text.text.clone()))
+        }
+        (Element::AnyText, SegmentKind::Text(text)) => Some(Capture::Text(text.text.clone())),
+        (Element::Regex(regex), SegmentKind::Text(text)) => {
+            RegexCapture::new(regex, &text.text).map(Capture::Regex)
+        }
+        (Element::At, SegmentKind::At(at)) => Some(Capture::At(at.clone())),
+        (Element::AtSelf, SegmentKind::At(at)) if at.user_id() == Some(self_id) => {
+            Some(Capture::At(at.clone()))
+        }
+        (Element::Image, SegmentKind::Image(media)) => Some(Capture::Image(media.clone())),
+        (Element::Reply, SegmentKind::Reply(reply)) => Some(Capture::Reply(reply.clone())),
+        _ => None,
+    }
+}
+
+#[test]
+fn match_segment_patterns() {
+    let quote = SegmentPattern::new()
+        .reply()
+        .optional(SegmentPattern::new().at_self())
+        .text("quote");
+
+    let msg = Message::new().add_reply(7).add_at("10").add_text(" quote ");
+    let captures = quote.matches(&msg, 10).expect("pattern matches");
+    assert_eq!(captures.reply_id(), Some(7));
+    assert_eq!(captures.ats()[0].user_id(), Some(10));
+    assert_eq!(captures.texts(), vec!["quote"]);
+
+    let msg = Message::new().add_reply(7).add_text("quo").add_text("te");
+    let captures = quote.matches(&msg, 10).expect("pattern matches");
+    assert_eq!(captures.get(1), None);
+    assert_eq!(captures.get(2), Some(&Capture::Text("quote".to_string())));
+
+    assert!(quote.matches(&msg.clone().add_text(" again"), 10).is_none());
+    assert!(
+        quote
+            .matches(
+                &Message::new().add_reply(7).add_at("11").add_text("quote"),
+                10
+            )
+            .is_none()
+    );
+
+    let ocr = SegmentPattern::new()
+        .image()
+        .regex(Regex::new(r"^ocr(?: (?<lang>\w+))?$").expect("valid regex"));
+    let msg = Message::new().add_image("a.png").add_text("\nocr en");
+    let captures = ocr.matches(&msg, 10).expect("pattern matches");
+    assert_eq!(captures.images()[0].file, "a.png");
+    assert_eq!(
+        captures.regex().expect("regex captured").name("lang"),
+        Some("en")
+    );
+    assert!(ocr.matches(&Message::from("ocr"), 10).is_none());
+}
@@ -0,0 +1,529 @@
+use super::{Message, Segment};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+/// 有类型的 segment，覆盖 OneBot v11 的所有消息段
+///
+/// 与 `Segment` 之间可以无损转换：未列出的字段保存在各结构体的 `extra` 中，
+/// 无法识别的类型或格式不正确的 segment 会原样保存在 `Unknown` 中。
+/// 部分实现会把数字字段发成数字，转换后会按 OneBot 标准变成字符串。
+///
+/// # Examples
+/// ```no_run
+/// use kovi::bot::message::{Segment, SegmentKind};
+///
+/// fn print_at(segment: &Segment) {
+///     let kind = SegmentKind::from(segment);
+///     if let SegmentKind::At(at) = kind {
+///         println!("at {:?}", at.user_id());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentKind {
+    Text(Text),
+    Face(Face),
+    Image(Media),
+    Record(Media),
+    Video(Media),
+    At(At),
+    /// 猜拳
+    Rps(Map<String, Value>),
+    /// 掷骰子
+    Dice(Map<String, Value>),
+    /// 窗口抖动
+    Shake(Map<String, Value>),
+    Poke(Poke),
+    Share(Share),
+    Contact(Contact),
+    Location(Location),
+    Music(Music),
+    Reply(Reply),
+    Forward(Forward),
+    Node(Node),
+    Xml(Xml),
+    Json(Json),
+    /// 实现自己扩展的，或格式不正确的 segment
+    Unknown(Segment),
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Text {
+    pub text: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Face {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// 图片、语音与短视频
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Media {
+    /// 文件名、绝对路径、网络 URL 或 Base64 编码
+    pub file: String,
+    /// 图片类型，`flash` 为闪照
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    /// 收到的文件的 URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// 发送网络文件时是否使用已缓存的文件，`1` 或 `0`
+    #[serde(
+        default,
+        deserialize_with = "opt_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cache: Option<String>,
+    /// 发送网络文件时是否通过代理下载，`1` 或 `0`
+    #[serde(
+        default,
+        deserialize_with = "opt_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub proxy: Option<String>,
+    /// 发送网络文件时下载的超时时间，秒
+    #[serde(
+        default,
+        deserialize_with = "opt_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<String>,
+    /// 语音是否变声，`1` 或 `0`
+    #[serde(
+        default,
+        deserialize_with = "opt_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub magic: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct At {
+    /// 被 at 的 QQ 号，`all` 表示全体成员
+    #[serde(deserialize_with = "string_or_number")]
+    pub qq: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl At {
+    /// 被 at 的 QQ 号，at 全体成员时返回 `None`
+    pub fn user_id(&self) -> Option<i64> {
+        self.qq.parse().ok()
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.qq == "all"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Poke {
+    #[serde(rename = "type", deserialize_with = "string_or_number")]
+    pub type_: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// 链接分享
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Share {
+    pub url: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// 推荐好友或群
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Contact {
+    /// `qq` 或 `group`
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Location {
+    #[serde(deserialize_with = "string_or_number")]
+    pub lat: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub lon: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// 音乐分享
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Music {
+    /// `qq`、`163`、`xm` 或 `custom`
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(
+        default,
+        deserialize_with = "opt_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Reply {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// 合并转发
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Forward {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// 合并转发节点，引用已有消息时只有 `id`，自定义节点时有 `user_id`、`nickname` 与 `content`
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Node {
+    #[serde(
+        default,
+        deserialize_with = "opt_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub id: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "opt_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub user_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<Message>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Xml {
+    pub data: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Json {
+    pub data: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        other => Err(serde::de::Error::custom(format!(
+            "expected string or number, found {other}"
+        ))),
+    }
+}
+
+fn opt_string_or_number<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    string_or_number(deserializer).map(Some)
+}
+
+impl From<Segment> for SegmentKind {
+    fn from(segment: Segment) -> Self {
+        fn parse<T: DeserializeOwned>(segment: Segment, kind: fn(T) -> SegmentKind) -> SegmentKind {
+            match serde_json::from_value(segment.data.clone()) {
+                Ok(data) => kind(data),
+                Err(_) => SegmentKind::Unknown(segment),
+            }
+        }
+
+        match segment.type_.as_str() {
+            "text" => parse(segment, SegmentKind::Text),
+            "face" => parse(segment, SegmentKind::Face),
+            "image" => parse(segment, SegmentKind::Image),
+            "record" => parse(segment, SegmentKind::Record),
+            "video" => parse(segment, SegmentKind::Video),
+            "at" => parse(segment, SegmentKind::At),
+            "rps" => parse(segment, SegmentKind::Rps),
+            "dice" => parse(segment, SegmentKind::Dice),
+            "shake" => parse(segment, SegmentKind::Shake),
+            "poke" => parse(segment, SegmentKind::Poke),
+            "share" => parse(segment, SegmentKind::Share),
+            "contact" => parse(segment, SegmentKind::Contact),
+            "location" => parse(segment, SegmentKind::Location),
+            "music" => parse(segment, SegmentKind::Music),
+            "reply" => parse(segment, SegmentKind::Reply),
+            "forward" => parse(segment, SegmentKind::Forward),
+            "node" => parse(segment, SegmentKind::Node),
+            "xml" => parse(segment, SegmentKind::Xml),
+            "json" => parse(segment, SegmentKind::Json),
+            _ => SegmentKind::Unknown(segment),
+        }
+    }
+}
+
+impl From<&Segment> for SegmentKind {
+    fn from(segment: &Segment) -> Self {
+        SegmentKind::from(segment.clone())
+    }
+}
+
+impl From<SegmentKind> for Segment {
+    fn from(kind: SegmentKind) -> Self {
+        fn to<T: Serialize>(type_: &str, data: T) -> Segment {
+            Segment::new(
+                type_,
+                serde_json::to_value(data).unwrap_or_else(|_| Value::Object(Map::new())),
+            )
+        }
+
+        match kind {
+            SegmentKind::Text(data) => to("text", data),
+            SegmentKind::Face(data) => to("face", data),
+            SegmentKind::Image(data) => to("image", data),
+            SegmentKind::Record(data) => to("record", data),
+            SegmentKind::Video(data) => to("video", data),
+            SegmentKind::At(data) => to("at", data),
+            SegmentKind::Rps(data) => to("rps", data),
+            SegmentKind::Dice(data) => to("dice", data),
+            SegmentKind::Shake(data) => to("shake", data),
+            SegmentKind::Poke(data) => to("poke", data),
+            SegmentKind::Share(data) => to("share", data),
+            SegmentKind::Contact(data) => to("contact", data),
+            SegmentKind::Location(data) => to("location", data),
+            SegmentKind::Music(data) => to("music", data),
+            SegmentKind::Reply(data) => to("reply", data),
+            SegmentKind::Forward(data) => to("forward", data),
+            SegmentKind::Node(data) => to("node", data),
+            SegmentKind::Xml(data) => to("xml", data),
+            SegmentKind::Json(data) => to("json", data),
+            SegmentKind::Unknown(segment) => segment,
+        }
+    }
+}
+
+impl From<SegmentKind> for Message {
+    fn from(kind: SegmentKind) -> Self {
+        Message::from(vec![Segment::from(kind)])
+    }
+}
+
+impl Segment {
+    /// 转换为有类型的 segment
+    pub fn kind(&self) -> SegmentKind {
+        SegmentKind::from(self)
+    }
+}
+
+impl Message {
+    /// 转换为有类型的 segment
+    pub fn kinds(&self) -> impl Iterator<Item = SegmentKind> + '_ {
+        self.iter().map(SegmentKind::from)
+    }
+
+    /// 消息中所有的图片
+    pub fn images(&self) -> Vec<Media> {
+        self.kinds()
+            .filter_map(|kind| match kind {
+                SegmentKind::Image(image) => Some(image),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 消息中所有的语音
+    pub fn records(&self) -> Vec<Media> {
+        self.kinds()
+            .filter_map(|kind| match kind {
+                SegmentKind::Record(record) => Some(record),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 消息中所有的短视频
+    pub fn videos(&self) -> Vec<Media> {
+        self.kinds()
+            .filter_map(|kind| match kind {
+                SegmentKind::Video(video) => Some(video),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 消息中所有的 at
+    pub fn ats(&self) -> Vec<At> {
+        self.kinds()
+            .filter_map(|kind| match kind {
+                SegmentKind::At(at) => Some(at),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 消息中所有的表情
+    pub fn faces(&self) -> Vec<Face> {
+        self.kinds()
+            .filter_map(|kind| match kind {
+                SegmentKind::Face(face) => Some(face),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 消息引用的消息 ID，没有引用时返回 `None`
+    pub fn reply_id(&self) -> Option<i32> {
+        self.kinds().find_map(|kind| match kind {
+            SegmentKind::Reply(reply) => reply.id.parse().ok(),
+            _ => None,
+        })
+    }
+
+    /// 合并转发的 ID，不是合并转发消息时返回 `None`
+    pub fn forward_id(&self) -> Option<String> {
+        self.kinds().find_map(|kind| match kind {
+            SegmentKind::Forward(forward) => Some(forward.id),
+            _ => None,
+        })
+    }
+
+    /// 消息中是否 at 了此用户，不含 at 全体成员
+    pub fn is_at(&self, user_id: i64) -> bool {
+        // 每条消息都会检查是否 at 了 Bot，直接看 `qq`，不转换 segment
+        self.iter().any(|segment| {
+            segment.type_ == "at"
+                && match segment.data.get("qq") {
+                    Some(Value::String(qq)) => qq.parse() == Ok(user_id),
+                    Some(Value::Number(qq)) => qq.as_i64() == Some(user_id),
+                    _ => false,
+                }
+        })
+    }
+
+    /// 消息中是否 at 了全体成员
+    pub fn is_at_all(&self) -> bool {
+        self.ats().iter().any(At::is_all)
+    }
+
+    /// 消息中 at 的所有用户，按出现顺序去重，不含 at 全体成员
+    pub fn mentioned_users(&self) -> Vec<i64> {
+        let mut users = Vec::new();
+        for user_id in self.ats().iter().filter_map(At::user_id) {
+            if !users.contains(&user_id) {
+                users.push(user_id);
+            }
+        }
+        users
+    }
+
+    /// 消息中的文字，去掉 at、引用等其他 segment，首尾空白会被去掉
+    pub fn text_without_mentions(&self) -> String {
+        let mut text = String::new();
+        for segment in self.iter() {
+            if segment.type_ == "text"
+                && let Some(t) = segment.data.get("text").and_then(Value::as_str)
+            {
+                text.push_str(t);
+            }
+        }
+        text.trim().to_string()
+    }
+}
+
+#[test]
+fn segment_kind_round_trip() {
+    use serde_json::json;
+
+    let msg = Message::from_value(json!([
+        {"type": "reply", "data": {"id": "123"}},
+        {"type": "text", "data": {"text": "hi"}},
+        {"type": "face", "data": {"id": "178"}},
+        {"type": "image", "data": {"file": "a.jpg", "type": "flash", "url": "http://a", "summary": "[图片]"}},
+        {"type": "record", "data": {"file": "a.amr", "magic": "0"}},
+        {"type": "video", "data": {"file": "a.mp4"}},
+        {"type": "at", "data": {"qq": "10001", "name": "n"}},
+        {"type": "at", "data": {"qq": "all"}},
+        {"type": "rps", "data": {}},
+        {"type": "dice", "data": {"result": "6"}},
+        {"type": "shake", "data": {}},
+        {"type": "poke", "data": {"type": "126", "id": "2003"}},
+        {"type": "share", "data": {"url": "http://a", "title": "t"}},
+        {"type": "contact", "data": {"type": "group", "id": "10"}},
+        {"type": "location", "data": {"lat": "39.8", "lon": "116.4"}},
+        {"type": "music", "data": {"type": "163", "id": "28949129"}},
+        {"type": "forward", "data": {"id": "abc"}},
+        {"type": "node", "data": {"user_id": "10001", "nickname": "n", "content": [{"type": "text", "data": {"text": "hi"}}]}},
+        {"type": "xml", "data": {"data": "<xml/>"}},
+        {"type": "json", "data": {"data": "{}"}},
+        {"type": "markdown", "data": {"content": "# hi"}},
+        {"type": "at", "data": {}},
+    ]))
+    .expect("valid json");
+
+    let kinds: Vec<SegmentKind> = msg.kinds().collect();
+    assert!(matches!(kinds[8], SegmentKind::Rps(_)));
+    assert!(matches!(kinds[20], SegmentKind::Unknown(_)));
+    assert!(matches!(kinds[21], SegmentKind::Unknown(_)));
+    let back = Message::from(kinds.into_iter().map(Segment::from).collect::<Vec<_>>());
+    assert_eq!(back, msg);
+
+    assert_eq!(msg.reply_id(), Some(123));
+    assert_eq!(msg.forward_id().as_deref(), Some("abc"));
+    assert_eq!(msg.images()[0].type_.as_deref(), Some("flash"));
+    assert_eq!(msg.images()[0].extra["summary"], "[图片]");
+    let ats = msg.ats();
+    assert_eq!(ats[0].user_id(), Some(10001));
+    assert!(ats[1].is_all());
+
+    // 数字会转换为字符串
+    let at = Segment::new("at", json!({"qq": 10001})).kind();
+    assert_eq!(
+        Segment::from(at),
+        Segment::new("at", json!({"qq": "10001"}))
+    );
+}
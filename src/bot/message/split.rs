@@ -0,0 +1,172 @@
+use super::Message;
+use serde::{Deserialize, Serialize};
+
+/// 过长消息的拆分，位于配置文件的 `[config.split]`
+///
+/// 对 `send_group_msg()`、`send_private_msg()` 与 `reply()` 等生效，拆分后的消息按顺序逐条发送。
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SplitConfig {
+    /// 是否拆分过长的消息
+    #[serde(default)]
+    pub enabled: bool,
+    /// 每条消息的最大长度，文字按字符计，其他 segment 各计 1
+    #[serde(default = "default_max_length")]
+    pub max_length: usize,
+    /// 拆分后超过此条数时，改为以一条合并转发发送，为 0 时不使用合并转发
+    #[serde(default)]
+    pub forward_over: usize,
+    /// 合并转发中显示的昵称
+    #[serde(default = "default_forward_name")]
+    pub forward_name: String,
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        SplitConfig {
+            enabled: false,
+            max_length: default_max_length(),
+            forward_over: 0,
+            forward_name: default_forward_name(),
+        }
+    }
+}
+
+fn default_max_length() -> usize {
+    2000
+}
+
+fn default_forward_name() -> String {
+    "Kovi".to_string()
+}
+
+/// 拆分发送的方式
+pub(crate) enum SplitSend {
+    Parts(Vec<Message>),
+    Forward { parts: Vec<Message>, name: String },
+}
+
+/// 按配置拆分要发送的消息，不需要拆分时返回 `None`
+pub(crate) fn split_for_send(msg: &Message, config: &SplitConfig) -> Option<SplitSend> {
+    if !config.enabled {
+        return None;
+    }
+    let parts = msg.split(config.max_length);
+    if parts.len() <= 1 {
+        None
+    } else if config.forward_over > 0 && parts.len() > config.forward_over {
+        Some(SplitSend::Forward {
+            parts,
+            name: config.forward_name.clone(),
+        })
+    } else {
+        Some(SplitSend::Parts(parts))
+    }
+}
+
+impl Message {
+    /// 按长度拆分消息，文字按字符计，其他 segment 各计 1
+    ///
+    /// 优先在 segment 之间与换行处拆分，单行过长时才从行中拆开。
+    pub fn split(&self, max_length: usize) -> Vec<Message> {
+        let max_length = max_length.max(1);
+        let mut parts = Vec::new();
+        let mut current = Message::new();
+        let mut len = 0;
+
+        for segment in self.iter() {
+            let text = match segment.data.get("text").and_then(|v| v.as_str()) {
+                Some(text) if segment.type_ == "text" => text,
+                _ => {
+                    if len + 1 > max_length && len > 0 {
+                        parts.push(std::mem::take(&mut current));
+                        len = 0;
+                    }
+                    current.push(segment.clone());
+                    len += 1;
+                    continue;
+                }
+            };
+
+            let mut rest = text;
+            while !rest.is_empty() {
+                let room = max_length - len;
+                let chars = rest.chars().count();
+                if chars <= room {
+                    current.push_text(rest);
+                    len += chars;
+                    break;
+                }
+
+                // 拆开处的换行由分条代替
+                let head = char_prefix(rest, room);
+                match head.rfind('\n') {
+                    Some(i) => {
+                        if i > 0 {
+                            current.push_text(&rest[..i]);
+                            len += 1;
+                        }
+                        rest = &rest[i + 1..];
+                    }
+                    // 放不下这一行时先换一条消息
+                    None if len > 0 => {}
+                    None => {
+                        current.push_text(head);
+                        len += 1;
+                        rest = &rest[head.len()..];
+                        rest = rest.strip_prefix('\n').unwrap_or(rest);
+                    }
+                }
+                if len > 0 {
+                    parts.push(std::mem::take(&mut current));
+                    len = 0;
+                }
+            }
+        }
+
+        if len > 0 {
+            parts.push(current);
+        }
+        parts
+    }
+}
+
+/// 前 `n` 个字符
+fn char_prefix(s: &str, n: usize) -> &str {
+    match s.char_indices().nth(n) {
+        Some((i, _)) => &s[..i],
+        None => s,
+    }
+}
+
+#[test]
+fn split_long_messages() {
+    let msg = Message::new()
+        .add_reply(1)
+        .add_text("line1\nline2\n")
+        .add_face(1)
+        .add_text("一二三四五六七八");
+
+    let parts = msg.split(8);
+    let texts: Vec<String> = parts.iter().map(Message::to_human_string).collect();
+    assert_eq!(
+        texts,
+        vec!["[reply]line1", "line2\n[face]", "一二三四五六七八",]
+    );
+    assert_eq!(parts[0].reply_id(), Some(1));
+
+    let texts: Vec<String> = msg.split(5).iter().map(Message::to_human_string).collect();
+    assert_eq!(
+        texts,
+        vec![
+            "[reply]",
+            "line1",
+            "line2",
+            "[face]",
+            "一二三四五",
+            "六七八",
+        ]
+    );
+
+    assert_eq!(msg.split(100), vec![msg.clone()]);
+    assert!(Message::new().split(10).is_empty());
+}
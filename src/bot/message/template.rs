@@ -0,0 +1,455 @@
+use super::Message;
+use super::markup::escape_text;
+use crate::error::MessageError;
+use crate::event::MsgEvent;
+use crate::utils::load_toml_data;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::CharIndices;
+
+/// 消息模板，渲染后通过 `Message::from_markup()` 生成消息
+///
+/// 模板的格式：
+///
+/// - `{name}` 替换为 `TemplateContext` 中的值，可以带点，例如 `{sender.nickname}`
+/// - `{if name}...{else}...{end}` 值存在且不为空、`0`、`false` 时使用前一段，`{if !name}` 相反，`{else}` 可以省略
+/// - `{count|item|items}` 值为 1 时使用前者，否则使用后者
+/// - 其他部分都是标记文本，例如 `@{123456}`、`{face:14}`，见 `Message::from_markup()`
+///
+/// # Examples
+/// ```no_run
+/// use kovi::bot::message::{MessageTemplate, TemplateContext};
+/// use kovi::{MessageError, MsgEvent};
+///
+/// async fn notify(event: &MsgEvent) -> Result<(), MessageError> {
+///     let template = MessageTemplate::new("{at_sender} 你有 {count} 条{count|消息|消息}{if group_id}，在群 {group_id}{end}")?;
+///     let ctx = TemplateContext::from(event).set("count", 3);
+///     event.reply(template.render(&ctx)?);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageTemplate {
+    source: String,
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Markup(String),
+    Value(String),
+    Plural {
+        name: String,
+        one: String,
+        other: String,
+    },
+    If {
+        name: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+enum Terminator {
+    Eof,
+    Else,
+    End,
+}
+
+impl MessageTemplate {
+    /// 解析模板，格式不正确时返回错误
+    pub fn new(source: &str) -> Result<Self, MessageError> {
+        let mut parser = Parser {
+            chars: source.char_indices().peekable(),
+        };
+        let nodes = match parser.parse()? {
+            (nodes, Terminator::Eof) => nodes,
+            (_, Terminator::Else) => return Err(template_error("{else} without {if}")),
+            (_, Terminator::End) => return Err(template_error("{end} without {if}")),
+        };
+        Ok(MessageTemplate {
+            source: source.to_string(),
+            nodes,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// 用上下文渲染模板，缺少需要的值或生成的标记文本不正确时返回错误
+    pub fn render(&self, ctx: &TemplateContext) -> Result<Message, MessageError> {
+        let mut markup = String::new();
+        render_nodes(&self.nodes, ctx, &mut markup)?;
+        Message::from_markup(&markup)
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn parse(&mut self) -> Result<(Vec<Node>, Terminator), MessageError> {
+        let mut nodes = Vec::new();
+        let mut markup = String::new();
+
+        while let Some((i, c)) = self.chars.next() {
+            match c {
+                '\\' => {
+                    markup.push(c);
+                    if let Some((_, c)) = self.chars.next() {
+                        markup.push(c);
+                    }
+                }
+                '{' => {
+                    let body = self.read_body(i)?;
+                    let body = body.trim();
+                    if let Some(name) = body.strip_prefix("if ") {
+                        flush(&mut nodes, &mut markup);
+                        let name = name.trim();
+                        let (negate, name) = match name.strip_prefix('!') {
+                            Some(name) => (true, name.trim()),
+                            None => (false, name),
+                        };
+                        check_name(name)?;
+                        let (then, end) = self.parse()?;
+                        let otherwise = match end {
+                            Terminator::Eof => return Err(template_error("unclosed {if}")),
+                            Terminator::End => Vec::new(),
+                            Terminator::Else => match self.parse()? {
+                                (otherwise, Terminator::End) => otherwise,
+                                _ => return Err(template_error("unclosed {if}")),
+                            },
+                        };
+                        nodes.push(Node::If {
+                            name: name.to_string(),
+                            negate,
+                            then,
+                            otherwise,
+                        });
+                    } else if body == "else" || body == "end" {
+                        flush(&mut nodes, &mut markup);
+                        let end = if body == "else" {
+                            Terminator::Else
+                        } else {
+                            Terminator::End
+                        };
+                        return Ok((nodes, end));
+                    } else if body.contains('|') {
+                        let parts: Vec<&str> = body.split('|').collect();
+                        let [name, one, other] = parts[..] else {
+                            return Err(template_error(&format!(
+                                "plural needs '{{name|one|other}}', found '{{{body}}}'"
+                            )));
+                        };
+                        check_name(name.trim())?;
+                        flush(&mut nodes, &mut markup);
+                        nodes.push(Node::Plural {
+                            name: name.trim().to_string(),
+                            one: one.to_string(),
+                            other: other.to_string(),
+                        });
+                    } else if body.contains(':')
+                        || body.bytes().all(|b| b.is_ascii_digit())
+                        || (body == "all" && markup.ends_with('@'))
+                    {
+                        // 标记文本
+                        markup.push('{');
+                        markup.push_str(body);
+                        markup.push('}');
+                    } else {
+                        check_name(body)?;
+                        flush(&mut nodes, &mut markup);
+                        nodes.push(Node::Value(body.to_string()));
+                    }
+                }
+                c => markup.push(c),
+            }
+        }
+        flush(&mut nodes, &mut markup);
+        Ok((nodes, Terminator::Eof))
+    }
+
+    /// 读取到匹配的 `}`，保留转义
+    fn read_body(&mut self, start: usize) -> Result<String, MessageError> {
+        let mut body = String::new();
+        while let Some((_, c)) = self.chars.next() {
+            match c {
+                '\\' => {
+                    body.push(c);
+                    if let Some((_, c)) = self.chars.next() {
+                        body.push(c);
+                    }
+                }
+                '}' => return Ok(body),
+                c => body.push(c),
+            }
+        }
+        Err(template_error(&format!("unclosed '{{' at byte {start}")))
+    }
+}
+
+fn flush(nodes: &mut Vec<Node>, markup: &mut String) {
+    if !markup.is_empty() {
+        nodes.push(Node::Markup(std::mem::take(markup)));
+    }
+}
+
+fn check_name(name: &str) -> Result<(), MessageError> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    {
+        Ok(())
+    } else {
+        Err(template_error(&format!("invalid placeholder '{name}'")))
+    }
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    ctx: &TemplateContext,
+    markup: &mut String,
+) -> Result<(), MessageError> {
+    for node in nodes {
+        match node {
+            Node::Markup(m) => markup.push_str(m),
+            Node::Value(name) => match ctx.values.get(name) {
+                Some(TemplateValue::Text(text)) => markup.push_str(&escape_text(text)),
+                Some(TemplateValue::Markup(m)) => markup.push_str(m),
+                None => return Err(missing(name)),
+            },
+            Node::Plural { name, one, other } => {
+                let value = ctx.text(name).ok_or_else(|| missing(name))?;
+                let is_one = value.trim().parse::<f64>().is_ok_and(|n| n == 1.0);
+                markup.push_str(if is_one { one } else { other });
+            }
+            Node::If {
+                name,
+                negate,
+                then,
+                otherwise,
+            } => {
+                if ctx.is_truthy(name) != *negate {
+                    render_nodes(then, ctx, markup)?;
+                } else {
+                    render_nodes(otherwise, ctx, markup)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn missing(name: &str) -> MessageError {
+    template_error(&format!("missing value for '{{{name}}}'"))
+}
+
+fn template_error(reason: &str) -> MessageError {
+    MessageError::ParseError(format!("template: {reason}"))
+}
+
+#[derive(Debug, Clone)]
+enum TemplateValue {
+    Text(String),
+    Markup(String),
+}
+
+/// 渲染模板时使用的值
+///
+/// 从 `MsgEvent` 生成时包含：`user_id`、`self_id`、`message_id`、`group_id`（群聊）、`text`、
+/// `sender.nickname`、`sender.card`、`sender.title`、`sender.name`（优先群名片）、
+/// `at_sender`（群聊时 at 发送人，私聊时为空）
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext {
+    values: HashMap<String, TemplateValue>,
+}
+
+impl TemplateContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置文字值，其中的 `{` 等字符会原样显示
+    pub fn set<T: Display>(mut self, key: &str, value: T) -> Self {
+        self.values
+            .insert(key.to_string(), TemplateValue::Text(value.to_string()));
+        self
+    }
+
+    /// 设置标记文本值，例如 `@{123456}`、`{image:https://...}`
+    pub fn set_markup(mut self, key: &str, markup: &str) -> Self {
+        self.values
+            .insert(key.to_string(), TemplateValue::Markup(markup.to_string()));
+        self
+    }
+
+    fn set_opt<T: Display>(self, key: &str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.set(key, value),
+            None => self,
+        }
+    }
+
+    fn text(&self, key: &str) -> Option<&str> {
+        match self.values.get(key)? {
+            TemplateValue::Text(text) | TemplateValue::Markup(text) => Some(text),
+        }
+    }
+
+    fn is_truthy(&self, key: &str) -> bool {
+        self.text(key)
+            .is_some_and(|v| !v.is_empty() && v != "0" && v != "false")
+    }
+}
+
+impl From<&MsgEvent> for TemplateContext {
+    fn from(event: &MsgEvent) -> Self {
+        let sender = &event.sender;
+        let name = sender
+            .card
+            .clone()
+            .filter(|card| !card.is_empty())
+            .or_else(|| sender.nickname.clone())
+            .unwrap_or_else(|| sender.user_id.to_string());
+        let at_sender = match event.group_id {
+            Some(_) => format!("@{{{}}}", event.user_id),
+            None => String::new(),
+        };
+
+        TemplateContext::new()
+            .set("user_id", event.user_id)
+            .set("self_id", event.self_id)
+            .set("message_id", event.message_id)
+            .set_opt("group_id", event.group_id)
+            .set_opt("text", event.text.as_ref())
+            .set_opt("sender.nickname", sender.nickname.as_ref())
+            .set_opt("sender.card", sender.card.as_ref())
+            .set_opt("sender.title", sender.title.as_ref())
+            .set("sender.name", name)
+            .set_markup("at_sender", &at_sender)
+    }
+}
+
+/// 一组以名称区分的模板
+///
+/// # Examples
+/// ```no_run
+/// use kovi::bot::message::{TemplateContext, TemplateSet};
+/// use kovi::{MsgEvent, RuntimeBot};
+///
+/// async fn welcome(bot: &RuntimeBot, event: &MsgEvent) -> Result<(), Box<dyn std::error::Error>> {
+///     let replies = TemplateSet::load(
+///         [("welcome", "欢迎 {at_sender}"), ("bye", "再见 {sender.name}")],
+///         bot.get_data_path().join("replies.toml"),
+///     )?;
+///     event.reply(replies.render("welcome", &TemplateContext::from(event))?);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TemplateSet {
+    templates: HashMap<String, MessageTemplate>,
+}
+
+impl TemplateSet {
+    /// 解析所有模板，有模板格式不正确时返回错误
+    pub fn new<I, K, V>(templates: I) -> Result<Self, MessageError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: AsRef<str>,
+    {
+        let mut set = TemplateSet::default();
+        for (key, source) in templates {
+            let key = key.into();
+            let template = MessageTemplate::new(source.as_ref())
+                .map_err(|e| MessageError::ParseError(format!("'{key}': {e}")))?;
+            set.templates.insert(key, template);
+        }
+        Ok(set)
+    }
+
+    /// 从 TOML 文件加载模板，文件不存在时写入 `defaults`，文件中缺少的模板使用 `defaults`
+    pub fn load<I, K, V, P>(defaults: I, file_path: P) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+        P: AsRef<Path>,
+    {
+        let defaults: HashMap<String, String> = defaults
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        let mut templates = load_toml_data(defaults.clone(), file_path)?;
+        for (key, source) in defaults {
+            templates.entry(key).or_insert(source);
+        }
+        Ok(Self::new(templates)?)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&MessageTemplate> {
+        self.templates.get(key)
+    }
+
+    /// 渲染名为 `key` 的模板
+    pub fn render(&self, key: &str, ctx: &TemplateContext) -> Result<Message, MessageError> {
+        self.get(key)
+            .ok_or_else(|| template_error(&format!("'{key}' not found")))?
+            .render(ctx)
+    }
+}
+
+#[test]
+fn render_templates() {
+    let template = MessageTemplate::new(
+        r"{at_sender} {sender.name} 有 {count} {count|apple|apples}{if group_id}，在群 {group_id}{else}，私聊{end}{if !vip} \{广告\}{end}{face:14}",
+    )
+    .expect("valid template");
+    let ctx = TemplateContext::new()
+        .set_markup("at_sender", "@{10001}")
+        .set("sender.name", "{Kovi}")
+        .set("count", 1)
+        .set("group_id", 20);
+    assert_eq!(
+        template.render(&ctx).expect("template renders"),
+        Message::new()
+            .add_at("10001")
+            .add_text(" {Kovi} 有 1 apple，在群 20 {广告}")
+            .add_face(14)
+    );
+    let ctx = ctx.set("count", 2).set("group_id", 0).set("vip", true);
+    assert_eq!(
+        template
+            .render(&ctx)
+            .expect("template renders")
+            .to_human_string(),
+        "[at] {Kovi} 有 2 apples，私聊[face]"
+    );
+    assert!(template.render(&TemplateContext::new()).is_err());
+
+    for bad in ["{if a}", "{end}", "{a|b}", "{bad name}", "{if a}{else}"] {
+        assert!(MessageTemplate::new(bad).is_err(), "{bad}");
+    }
+
+    let path = std::env::temp_dir().join("kovi_render_templates.toml");
+    std::fs::write(&path, "hi = \"你好 {user_id}\"").expect("write temp file");
+    let set = TemplateSet::load([("hi", "hi"), ("bye", "bye")], &path).expect("load templates");
+    std::fs::remove_file(&path).expect("remove temp file");
+    let ctx = TemplateContext::new().set("user_id", 1);
+    assert_eq!(
+        set.render("hi", &ctx).expect("template renders"),
+        Message::from("你好 1")
+    );
+    assert_eq!(
+        set.render("bye", &ctx).expect("template renders"),
+        Message::from("bye")
+    );
+    assert!(set.render("none", &ctx).is_err());
+}
@@ -0,0 +1,172 @@
+use crate::bot::BotInformation;
+use crate::bot::plugin_builder::event::Event;
+use crate::event::{Role, message_sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 触发监听需要的权限
+///
+/// Bot 的管理员拥有所有群内的权限与所有自定义身份，主管理员拥有所有权限。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Permission {
+    /// 所有人
+    Everyone,
+    /// 群管理员与群主
+    GroupAdmin,
+    /// 群主
+    GroupOwner,
+    /// Bot 的主管理员与副管理员
+    BotAdmin,
+    /// Bot 的主管理员
+    MainAdmin,
+    /// 拥有此自定义身份的用户，见 `RuntimeBot::grant_role()`
+    Role(String),
+}
+
+impl Permission {
+    /// 事件的触发者是否拥有此权限
+    ///
+    /// 取不到触发者的事件只满足 `Permission::Everyone`。
+    pub fn check(&self, event: &dyn Event, bot_info: &BotInformation) -> bool {
+        if *self == Permission::Everyone {
+            return true;
+        }
+        let Some(user_id) = event.event_user_id() else {
+            return false;
+        };
+        if bot_info.main_admin == user_id {
+            return true;
+        }
+        if *self == Permission::MainAdmin {
+            return false;
+        }
+        if bot_info.deputy_admins.contains(&user_id) {
+            return true;
+        }
+
+        let role = message_sender(event).and_then(|sender| sender.role);
+        match self {
+            Permission::GroupAdmin => role >= Some(Role::Admin),
+            Permission::GroupOwner => role == Some(Role::Owner),
+            Permission::Role(name) => {
+                bot_info
+                    .roles
+                    .has_role(name, user_id, event.event_group_id())
+            }
+            Permission::Everyone | Permission::BotAdmin | Permission::MainAdmin => false,
+        }
+    }
+}
+
+/// 自定义身份的一次授予
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RoleGrant {
+    pub user_id: i64,
+    /// 生效的群，为空时在所有群与私聊中生效
+    #[serde(default)]
+    pub groups: Vec<i64>,
+}
+
+/// Bot 的所有自定义身份，以身份名为键
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Roles(HashMap<String, Vec<RoleGrant>>);
+
+impl Roles {
+    /// 用户在此群内是否拥有此身份，`group_id` 为 `None` 时只认在所有群生效的授予
+    pub fn has_role(&self, role: &str, user_id: i64, group_id: Option<i64>) -> bool {
+        self.get(role).iter().any(|grant| {
+            grant.user_id == user_id
+                && (grant.groups.is_empty()
+                    || group_id.is_some_and(|group_id| grant.groups.contains(&group_id)))
+        })
+    }
+
+    /// 获取此身份的所有授予
+    pub fn get(&self, role: &str) -> &[RoleGrant] {
+        self.0.get(role).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// 获取用户拥有的所有身份
+    pub fn roles_of(&self, user_id: i64) -> Vec<(&str, &RoleGrant)> {
+        self.0
+            .iter()
+            .flat_map(|(role, grants)| grants.iter().map(move |grant| (role.as_str(), grant)))
+            .filter(|(_, grant)| grant.user_id == user_id)
+            .collect()
+    }
+
+    pub(crate) fn grant(&mut self, role: &str, user_id: i64, groups: Vec<i64>) {
+        let grants = self.0.entry(role.to_string()).or_default();
+        match grants.iter_mut().find(|grant| grant.user_id == user_id) {
+            // 已经在所有群生效
+            Some(grant) if grant.groups.is_empty() => {}
+            Some(grant) if groups.is_empty() => grant.groups.clear(),
+            Some(grant) => {
+                for group in groups {
+                    if !grant.groups.contains(&group) {
+                        grant.groups.push(group);
+                    }
+                }
+            }
+            None => grants.push(RoleGrant { user_id, groups }),
+        }
+    }
+
+    pub(crate) fn revoke(&mut self, role: &str, user_id: i64, groups: &[i64]) {
+        let Some(grants) = self.0.get_mut(role) else {
+            return;
+        };
+        grants.retain_mut(|grant| {
+            if grant.user_id != user_id {
+                return true;
+            }
+            if groups.is_empty() {
+                return false;
+            }
+            if grant.groups.is_empty() {
+                // 在所有群生效的授予无法只撤销其中几个群
+                return true;
+            }
+            grant.groups.retain(|group| !groups.contains(group));
+            !grant.groups.is_empty()
+        });
+        if grants.is_empty() {
+            self.0.remove(role);
+        }
+    }
+}
+
+#[test]
+fn role_grants_are_scoped() {
+    let mut roles = Roles::default();
+    roles.grant("moderator", 1, vec![10]);
+    roles.grant("moderator", 1, vec![20]);
+    roles.grant("moderator", 2, Vec::new());
+
+    assert!(roles.has_role("moderator", 1, Some(20)));
+    assert!(!roles.has_role("moderator", 1, Some(30)));
+    assert!(!roles.has_role("moderator", 1, None));
+    assert!(roles.has_role("moderator", 2, Some(30)));
+    assert!(roles.has_role("moderator", 2, None));
+
+    roles.revoke("moderator", 1, &[10]);
+    assert!(!roles.has_role("moderator", 1, Some(10)));
+    assert!(roles.has_role("moderator", 1, Some(20)));
+
+    roles.revoke("moderator", 2, &[30]);
+    assert!(roles.has_role("moderator", 2, Some(30)));
+    roles.revoke("moderator", 2, &[]);
+    roles.revoke("moderator", 1, &[20]);
+    assert!(roles.get("moderator").is_empty());
+
+    let roles: Roles = toml::from_str(
+        r#"
+        [[moderator]]
+        user_id = 1
+        groups = [10]
+        "#,
+    )
+    .expect("valid toml");
+    assert!(roles.has_role("moderator", 1, Some(10)));
+}
@@ -1,5 +1,8 @@
-use super::{Bot, handler::KoviEvent};
-use crate::{PluginBuilder, bot::handler::InternalInternalEvent, types::ApiAndOneshot};
+use super::{Bot, SnapshotCell, handler::KoviEvent};
+use crate::{
+    PluginBuilder, bot::handler::InternalInternalEvent, plugin::health::install_panic_hook,
+    types::ApiAndOneshot,
+};
 use log::error;
 use parking_lot::RwLock;
 use std::{
@@ -35,8 +38,22 @@ impl Bot {
     /// 运行bot
     ///
     /// **注意此函数会阻塞, 直到Bot连接失效，或者有退出信号传入程序**
-    pub fn run(self) {
+    pub fn run(mut self) {
         let server = self.information.server.clone();
+        let snapshot = self.snapshot.clone();
+        let (event_channel_capacity, api_channel_capacity) = self.channel_capacity;
+
+        // 插件出错时需要记录 backtrace
+        install_panic_hook();
+
+        //处理连接，从msg_tx返回消息
+        let (event_tx, mut event_rx): (
+            mpsc::Sender<InternalInternalEvent>,
+            mpsc::Receiver<InternalInternalEvent>,
+        ) = mpsc::channel(event_channel_capacity);
+
+        self.event_tx = Some(event_tx.downgrade());
+        self.publish_snapshot();
 
         let bot = Arc::new(RwLock::new(self));
 
@@ -46,15 +63,9 @@ impl Bot {
             //     tokio::sync::broadcast::Receiver<Arc<dyn super::plugin_builder::event::Event>>,
             // ) = tokio::sync::broadcast::channel(32);
 
-            //处理连接，从msg_tx返回消息
-            let (event_tx, mut event_rx): (
-                mpsc::Sender<InternalInternalEvent>,
-                mpsc::Receiver<InternalInternalEvent>,
-            ) = mpsc::channel(32);
-
             // 接收插件的api
             let (api_tx, api_rx): (mpsc::Sender<ApiAndOneshot>, mpsc::Receiver<ApiAndOneshot>) =
-                mpsc::channel(32);
+                mpsc::channel(api_channel_capacity);
 
             // 连接
             let connect_task = RT.spawn({
@@ -91,15 +102,9 @@ impl Bot {
             let mut drop_task = None;
             //处理事件，每个事件都会来到这里
             while let Some(event) = event_rx.recv().await {
-                let api_tx = api_tx.clone();
-                let bot = bot.clone();
-
-                // Drop为关闭事件，所以要等待，其他的不等待
-                if let InternalInternalEvent::KoviEvent(KoviEvent::Drop) = event {
-                    drop_task = Some(RT.spawn(Self::handler_event(bot, event, api_tx)));
+                if let Some(task) = Self::receive_event(&bot, &snapshot, event, &api_tx) {
+                    drop_task = Some(task);
                     break;
-                } else {
-                    RT.spawn(Self::handler_event(bot, event, api_tx));
                 }
             }
             if let Some(drop_task) = drop_task {
@@ -115,6 +120,36 @@ impl Bot {
         RT.block_on(async_task);
     }
 
+    /// 处理接收循环收到的事件，收到关闭事件时返回关闭的任务
+    ///
+    /// OneBot 事件直接在接收循环内分发，顺序处理的排队才能与事件到达的顺序一致。
+    pub(crate) fn receive_event(
+        bot: &Arc<RwLock<Self>>,
+        snapshot: &Arc<SnapshotCell>,
+        event: InternalInternalEvent,
+        api_tx: &mpsc::Sender<ApiAndOneshot>,
+    ) -> Option<JoinHandle<()>> {
+        match event {
+            InternalInternalEvent::OneBotEvent(msg) => {
+                Self::handler_internal_event(snapshot.load(), msg, api_tx.clone());
+                None
+            }
+            // Drop为关闭事件，所以要等待，其他的不等待
+            event @ InternalInternalEvent::KoviEvent(KoviEvent::Drop) => Some(RT.spawn(
+                Self::handler_event(bot.clone(), snapshot.clone(), event, api_tx.clone()),
+            )),
+            event => {
+                RT.spawn(Self::handler_event(
+                    bot.clone(),
+                    snapshot.clone(),
+                    event,
+                    api_tx.clone(),
+                ));
+                None
+            }
+        }
+    }
+
     // 运行所有main()
     fn run_mains(bot: Arc<RwLock<Self>>, api_tx: mpsc::Sender<ApiAndOneshot>) {
         let bot_ = bot.read();
@@ -235,3 +270,88 @@ pub(crate) async fn exit_signal_check(tx: Sender<InternalInternalEvent>) {
 async fn handler_second_time_exit_signal() {
     exit(1)
 }
+
+#[test]
+fn sequential_listen_handles_events_in_arrival_order() {
+    use crate::bot::plugin_builder::ListenOptions;
+    use crate::bot::plugin_builder::event::Event;
+    use crate::bot::{BotInformation, Host, KoviConf, Server};
+    use crate::event::{InternalEvent, OneBotEvent};
+    use crate::plugin::limiter::Limiter;
+    use crate::plugin::{ConcurrencyLimit, OverflowPolicy, Plugin, SequentialKey};
+    use std::time::Duration;
+
+    struct Chat {
+        group_id: i64,
+        index: u64,
+    }
+    impl Event for Chat {
+        fn de(
+            event: &InternalEvent,
+            _: &BotInformation,
+            _: &Sender<ApiAndOneshot>,
+        ) -> Option<Self> {
+            let json = event.json()?;
+            Some(Chat {
+                group_id: json.get("group_id")?.as_i64()?,
+                index: json.get("index")?.as_u64()?,
+            })
+        }
+
+        fn event_group_id(&self) -> Option<i64> {
+            Some(self.group_id)
+        }
+    }
+
+    let conf = KoviConf::new(
+        1,
+        None,
+        Server::new(
+            Host::IpAddr([127, 0, 0, 1].into()),
+            8081,
+            String::new(),
+            false,
+        ),
+        false,
+    );
+    let mut bot = Bot::build(&conf);
+    let (handled_tx, handled_rx) = std::sync::mpsc::channel();
+    let mut plugin = Plugin::new("p", "0.0.1", Arc::new(|| Box::pin(async {})));
+    plugin.limiter = Some(Arc::new(Limiter::new(
+        "p".to_string(),
+        ConcurrencyLimit::new(1, 0, OverflowPolicy::Queue),
+    )));
+    Arc::make_mut(&mut plugin.listen).on(
+        ListenOptions::new().sequential(SequentialKey::group()),
+        move |event: Arc<Chat>| {
+            let handled_tx = handled_tx.clone();
+            async move {
+                // 越早的事件处理越慢，没有排队时会倒序完成
+                tokio::time::sleep(Duration::from_millis(20 - event.index * 2)).await;
+                let _ = handled_tx.send(event.index);
+            }
+        },
+    );
+    bot.mount_plugin(plugin);
+    let snapshot = bot.snapshot.clone();
+    let bot = Arc::new(RwLock::new(bot));
+
+    let (api_tx, _api_rx) = mpsc::channel(1);
+    let _guard = RT.enter();
+    for index in 0..10 {
+        let raw = serde_json::json!({ "group_id": 1, "index": index }).to_string();
+        let event =
+            InternalInternalEvent::OneBotEvent(InternalEvent::OneBotEvent(OneBotEvent::new(raw)));
+        assert!(Bot::receive_event(&bot, &snapshot, event, &api_tx).is_none());
+    }
+
+    // 并发上限为 1 时也不会卡住
+    let handled: Vec<_> = (0..10)
+        .map(|_| {
+            handled_rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("event handled")
+        })
+        .collect();
+    assert_eq!(handled, (0..10).collect::<Vec<_>>());
+}
@@ -1,10 +1,15 @@
 use crate::types::{ApiAndOneshot, ApiOneshotReceiver, ApiOneshotSender};
 
+use super::message::split::{SplitConfig, SplitSend, split_for_send};
+use super::message::{ForwardMessage, Message};
 use super::{ApiReturn, Bot, Host, SendApi};
+use crate::RT;
 use log::error;
-use parking_lot::RwLock;
-use serde_json::Value;
-use std::sync::Weak;
+use parking_lot::{Mutex, RwLock};
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 use tokio::sync::{mpsc, oneshot};
 
 pub mod kovi_api;
@@ -14,11 +19,15 @@ pub use kovi_api::SetAdmin;
 
 /// 运行时的Bot，可以用来发送api，需要从PluginBuilder的.get_runtime_bot()获取。
 /// # Examples
-/// ```
-/// let bot = PluginBuilder::get_runtime_bot();
-/// let user_id = bot.main_admin;
+/// ```no_run
+/// use kovi::PluginBuilder;
 ///
-/// bot.send_private_msg(user_id, "bot online")
+/// async fn start() {
+///     let bot = PluginBuilder::get_runtime_bot();
+///     if let Ok(user_id) = bot.get_main_admin() {
+///         bot.send_private_msg(user_id, "bot online");
+///     }
+/// }
 /// ```
 #[derive(Clone)]
 pub struct RuntimeBot {
@@ -90,6 +99,228 @@ pub fn send_api_request_with_forget(api_tx: &mpsc::Sender<ApiAndOneshot>, send_a
     };
 }
 
+/// 消息的发送对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum MsgTarget {
+    Group(i64),
+    Private(i64),
+}
+
+impl MsgTarget {
+    pub(crate) fn new(group_id: Option<i64>, user_id: i64) -> Self {
+        match group_id {
+            Some(group_id) => MsgTarget::Group(group_id),
+            None => MsgTarget::Private(user_id),
+        }
+    }
+}
+
+/// 等待拆分后的消息发送响应的时间，超时后继续发送下一条
+pub(crate) const SPLIT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Bot 发送拆分消息的队列，所有克隆共享同一个
+///
+/// 每个发送对象各自排队，拆分后的消息逐条等待响应，互不影响。不需要拆分的消息直接发送，不经过队列。
+#[derive(Clone, Default)]
+pub(crate) struct MsgQueue(Arc<MsgQueueInner>);
+
+#[derive(Default)]
+struct MsgQueueInner {
+    split: SplitConfig,
+    response_timeout: Duration,
+    /// 正在发送的对象，以及在它后面排队的消息
+    targets: Mutex<HashMap<MsgTarget, VecDeque<QueuedMsg>>>,
+}
+
+struct QueuedMsg {
+    api_tx: mpsc::Sender<ApiAndOneshot>,
+    target: MsgTarget,
+    /// 合并转发节点的 QQ 号，为 `None` 时先获取登录号
+    self_id: Option<i64>,
+    split: SplitSend,
+}
+
+impl std::fmt::Debug for MsgQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MsgQueue")
+            .field("split", &self.0.split)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MsgQueue {
+    pub(crate) fn new(split: SplitConfig, response_timeout: Duration) -> Self {
+        MsgQueue(Arc::new(MsgQueueInner {
+            split,
+            response_timeout,
+            targets: Mutex::default(),
+        }))
+    }
+
+    /// 发送消息，需要拆分时按 `SplitConfig` 拆分，`send_api` 为不拆分时发送的请求
+    pub(crate) fn send(
+        &self,
+        api_tx: &mpsc::Sender<ApiAndOneshot>,
+        target: MsgTarget,
+        msg: &Message,
+        send_api: SendApi,
+        self_id: Option<i64>,
+    ) {
+        let Some(split) = split_for_send(msg, &self.0.split) else {
+            send_api_request_with_forget(api_tx, send_api);
+            return;
+        };
+        let msg = QueuedMsg {
+            api_tx: api_tx.clone(),
+            target,
+            self_id,
+            split,
+        };
+
+        let mut targets = self.0.targets.lock();
+        match targets.get_mut(&target) {
+            Some(queue) => queue.push_back(msg),
+            None => {
+                targets.insert(target, VecDeque::new());
+                RT.spawn(self.clone().run(target, msg));
+            }
+        }
+    }
+
+    /// 发送此对象的消息，直到它的队列为空
+    async fn run(self, target: MsgTarget, mut msg: QueuedMsg) {
+        loop {
+            msg.send(self.0.response_timeout).await;
+            let next = {
+                let mut targets = self.0.targets.lock();
+                let next = targets.get_mut(&target).and_then(VecDeque::pop_front);
+                if next.is_none() {
+                    targets.remove(&target);
+                }
+                next
+            };
+            match next {
+                Some(next) => msg = next,
+                None => return,
+            }
+        }
+    }
+}
+
+impl RuntimeBot {
+    /// 经过 Bot 的消息队列发送，Bot 已经不存在时直接发送
+    fn send_msg_queued(&self, target: MsgTarget, msg: &Message, send_api: SendApi) {
+        match self.bot.upgrade() {
+            Some(bot) => {
+                let queue = bot.read().msg_queue.clone();
+                queue.send(&self.api_tx, target, msg, send_api, None);
+            }
+            None => send_api_request_with_forget(&self.api_tx, send_api),
+        }
+    }
+}
+
+impl QueuedMsg {
+    async fn send(self, timeout: Duration) {
+        let QueuedMsg {
+            api_tx,
+            target,
+            self_id,
+            split,
+        } = self;
+        let (message_type, id_key, id) = match target {
+            MsgTarget::Group(group_id) => ("group", "group_id", group_id),
+            MsgTarget::Private(user_id) => ("private", "user_id", user_id),
+        };
+
+        let parts = match split {
+            SplitSend::Parts(parts) => parts,
+            SplitSend::Forward { parts, name } => {
+                let uin = match self_id {
+                    Some(self_id) => Some(self_id),
+                    None => {
+                        let api_rx =
+                            send_api_request(&api_tx, SendApi::new("get_login_info", json!({})));
+                        match await_response(api_rx, timeout).await {
+                            Some(Ok(v)) => v.data.get("user_id").and_then(Value::as_i64),
+                            Some(Err(e)) => {
+                                error!("Failed to get the login info: {e:?}");
+                                None
+                            }
+                            None => None,
+                        }
+                    }
+                };
+                match uin {
+                    Some(uin) => {
+                        log::debug!(
+                            "Message to {message_type} {id} split into {} parts, sent as forward",
+                            parts.len()
+                        );
+                        let forward = parts
+                            .into_iter()
+                            .fold(ForwardMessage::new(), |forward, part| {
+                                forward.add_custom(&name, uin, part)
+                            });
+                        let mut params = json!({ "messages": Message::from(forward) });
+                        params[id_key] = json!(id);
+                        let send_api =
+                            SendApi::new(&format!("send_{message_type}_forward_msg"), params);
+                        let api_rx = send_api_request(&api_tx, send_api);
+                        if let Some(Err(e)) = await_response(api_rx, timeout).await {
+                            error!(
+                                "Failed to send a forward message to {message_type} {id}: {e:?}"
+                            );
+                        }
+                        return;
+                    }
+                    None => {
+                        error!(
+                            "No login uin for the forward message to {message_type} {id}, sending its parts one by one"
+                        );
+                        parts
+                    }
+                }
+            }
+        };
+
+        log::debug!(
+            "Message to {message_type} {id} split into {} parts",
+            parts.len()
+        );
+        for part in parts {
+            let mut params = json!({
+                "message_type":message_type,
+                "message":part,
+            });
+            params[id_key] = json!(id);
+            // 等上一条发送完成，保证顺序
+            let api_rx = send_api_request(&api_tx, SendApi::new("send_msg", params));
+            if let Some(Err(e)) = await_response(api_rx, timeout).await {
+                error!("Failed to send a split message to {message_type} {id}: {e:?}");
+            }
+        }
+    }
+}
+
+/// 等待 API 响应，超时或通道关闭时返回 `None`
+async fn await_response(
+    api_rx: ApiOneshotReceiver,
+    timeout: Duration,
+) -> Option<Result<ApiReturn, ApiReturn>> {
+    match tokio::time::timeout(timeout, api_rx).await {
+        Ok(Ok(v)) => Some(v),
+        Ok(Err(e)) => {
+            error!("{e}");
+            None
+        }
+        Err(_) => {
+            log::warn!("No API response in {timeout:?}, continue sending");
+            None
+        }
+    }
+}
+
 /// 一个异步 Future ，传入一个 API 通道，可以用于等待在 Kovi 中缓存好的 API 响应。
 pub async fn send_api_await_response(api_rx: ApiOneshotReceiver) -> Result<ApiReturn, ApiReturn> {
     match api_rx.await {
@@ -136,3 +367,60 @@ pub trait CanSendApi {
         send_api_request_with_response(self.__get_api_tx(), send_api)
     }
 }
+
+#[test]
+fn msg_queue_orders_each_target_without_blocking_others() {
+    let queue = MsgQueue::new(
+        SplitConfig {
+            enabled: true,
+            max_length: 5,
+            ..Default::default()
+        },
+        Duration::from_millis(50),
+    );
+    let (api_tx, mut api_rx) = mpsc::channel(16);
+    for (target, text) in [
+        (MsgTarget::Group(1), "一二三四五六七八"),
+        (MsgTarget::Group(2), "一二三四五六七八"),
+        (MsgTarget::Group(1), "short"),
+    ] {
+        let msg = Message::from(text);
+        let send_api = SendApi::new("send_msg", json!({ "message": msg }));
+        queue.send(&api_tx, target, &msg, send_api, None);
+    }
+
+    // 不回复任何响应，每个对象的下一条在超时后发送
+    let sent = RT.block_on(async {
+        let mut sent = Vec::new();
+        for _ in 0..5 {
+            let (send_api, _api_rx) = api_rx.recv().await.expect("message sent");
+            let msg: Message =
+                serde_json::from_value(send_api.params["message"].clone()).expect("message");
+            sent.push((send_api.params["group_id"].as_i64(), msg.to_human_string()));
+        }
+        sent
+    });
+
+    // 不需要拆分的消息直接发送，不等前面拆分的消息
+    let short = sent
+        .iter()
+        .position(|(_, text)| text == "short")
+        .expect("short message sent");
+    let second_part = sent
+        .iter()
+        .position(|msg| *msg == (Some(1), "六七八".to_string()))
+        .expect("second part sent");
+    assert!(short < second_part);
+    for group_id in [1, 2] {
+        let texts: Vec<_> = sent
+            .iter()
+            .filter(|(id, _)| *id == Some(group_id))
+            .map(|(_, text)| text.as_str())
+            .collect();
+        assert_eq!(texts, ["一二三四五", "六七八"]);
+    }
+
+    // 发送完后不再占用对象
+    RT.block_on(async { tokio::time::sleep(Duration::from_millis(200)).await });
+    assert!(queue.0.targets.lock().is_empty());
+}
@@ -1,5 +1,14 @@
 use super::RuntimeBot;
-use crate::{Bot, PluginBuilder, RT, error::BotError, plugin::PluginInfo, types::ApiAndOneshot};
+use crate::{
+    Bot, PluginBuilder, RT,
+    bot::{
+        message::{MediaConfig, SplitConfig},
+        permission::Roles,
+    },
+    error::BotError,
+    plugin::{PluginDisabledIn, PluginInfo},
+    types::ApiAndOneshot,
+};
 use parking_lot::RwLock;
 use std::{path::PathBuf, sync::Arc};
 use tokio::sync::mpsc;
@@ -41,15 +50,163 @@ pub enum SetAccessControlList {
     Changes(Vec<i64>),
 }
 
+#[cfg(feature = "plugin-access-control")]
+#[derive(Debug, Clone)]
+pub enum SetAccessRule {
+    /// 增加一条规则，已有相同群与用户的规则时替换它
+    Add(AccessRule),
+    /// 增加多条规则
+    Adds(Vec<AccessRule>),
+    /// 移除群与用户都相同的规则，不比较过期时间
+    Remove(AccessRule),
+    /// 移除多条规则
+    Removes(Vec<AccessRule>),
+    /// 替换规则成这些规则
+    Changes(Vec<AccessRule>),
+}
+
 #[cfg(feature = "plugin-access-control")]
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct AccessList {
     pub friends: HashSet<i64>,
     pub groups: HashSet<i64>,
+    /// 组合了群与用户的规则，与 `friends`、`groups` 一同生效
+    #[serde(default)]
+    pub rules: Vec<AccessRule>,
+}
+
+#[cfg(feature = "plugin-access-control")]
+impl AccessList {
+    /// 按名单模式判断是否允许，`now` 为当前时间戳，秒
+    ///
+    /// 在群内时按群名单判断，否则按好友名单判断，再加上名单中的规则。没有群与用户时总是允许。
+    pub fn allows(
+        &self,
+        mode: AccessControlMode,
+        group_id: Option<i64>,
+        user_id: Option<i64>,
+        now: i64,
+    ) -> bool {
+        let listed = match (group_id, user_id) {
+            (Some(group_id), _) => self.groups.contains(&group_id),
+            (None, Some(user_id)) => self.friends.contains(&user_id),
+            (None, None) => return true,
+        } || self
+            .rules
+            .iter()
+            .any(|rule| rule.matches(group_id, user_id, now));
+
+        match mode {
+            AccessControlMode::WhiteList => listed,
+            AccessControlMode::BlackList => !listed,
+        }
+    }
 }
 
+/// 一条名单规则
+///
+/// 事件在 `group` 内（为 `None` 时不限群），且由 `users` 中的用户（为空时不限用户）触发时，命中此规则。
 #[cfg(feature = "plugin-access-control")]
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AccessRule {
+    pub group: Option<i64>,
+    #[serde(default)]
+    pub users: Vec<i64>,
+    /// 过期的时间戳，秒，为 `None` 时永不过期
+    pub expires_at: Option<i64>,
+}
+
+#[cfg(feature = "plugin-access-control")]
+impl AccessRule {
+    /// 整个群
+    pub fn group(group_id: i64) -> Self {
+        AccessRule {
+            group: Some(group_id),
+            users: Vec::new(),
+            expires_at: None,
+        }
+    }
+
+    /// 用户，在所有群与私聊中
+    pub fn user(user_id: i64) -> Self {
+        AccessRule {
+            group: None,
+            users: vec![user_id],
+            expires_at: None,
+        }
+    }
+
+    /// 群内的这些用户
+    pub fn users_in_group(group_id: i64, users: Vec<i64>) -> Self {
+        AccessRule {
+            group: Some(group_id),
+            users,
+            expires_at: None,
+        }
+    }
+
+    /// 从现在起经过 `duration` 后过期
+    pub fn expires_in(mut self, duration: std::time::Duration) -> Self {
+        let secs = i64::try_from(duration.as_secs()).unwrap_or(i64::MAX);
+        self.expires_at = Some(chrono::Local::now().timestamp().saturating_add(secs));
+        self
+    }
+
+    /// 在此时间戳（秒）过期
+    pub fn expires_at(mut self, timestamp: i64) -> Self {
+        self.expires_at = Some(timestamp);
+        self
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// 事件是否命中此规则，`now` 为当前时间戳，秒
+    pub fn matches(&self, group_id: Option<i64>, user_id: Option<i64>, now: i64) -> bool {
+        if self.is_expired(now) {
+            return false;
+        }
+        if self.group.is_some() && self.group != group_id {
+            return false;
+        }
+        self.users.is_empty() || user_id.is_some_and(|user_id| self.users.contains(&user_id))
+    }
+
+    fn same_target(&self, other: &AccessRule) -> bool {
+        self.group == other.group && self.users == other.users
+    }
+}
+
+/// 修改规则，同时清理已经过期的规则
+#[cfg(feature = "plugin-access-control")]
+pub(crate) fn apply_access_rules(rules: &mut Vec<AccessRule>, change: SetAccessRule) {
+    fn add(rules: &mut Vec<AccessRule>, rule: AccessRule) {
+        match rules.iter_mut().find(|r| r.same_target(&rule)) {
+            Some(r) => *r = rule,
+            None => rules.push(rule),
+        }
+    }
+    match change {
+        SetAccessRule::Add(rule) => add(rules, rule),
+        SetAccessRule::Adds(new_rules) => {
+            for rule in new_rules {
+                add(rules, rule);
+            }
+        }
+        SetAccessRule::Remove(rule) => rules.retain(|r| !r.same_target(&rule)),
+        SetAccessRule::Removes(removes) => {
+            rules.retain(|r| !removes.iter().any(|rule| r.same_target(rule)))
+        }
+        SetAccessRule::Changes(new_rules) => *rules = new_rules,
+    }
+
+    let now = chrono::Local::now().timestamp();
+    rules.retain(|rule| !rule.is_expired(now));
+}
+
+#[cfg(feature = "plugin-access-control")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum AccessControlMode {
     BlackList,
     WhiteList,
@@ -86,6 +243,7 @@ impl RuntimeBot {
         };
 
         plugin.access_control = enable;
+        bot.publish_snapshot();
 
         Ok(())
     }
@@ -118,6 +276,7 @@ impl RuntimeBot {
         };
 
         plugin.list_mode = access_control_mode;
+        bot.publish_snapshot();
 
         Ok(())
     }
@@ -183,7 +342,7 @@ impl RuntimeBot {
             }
             // 添加多个用户到名单
             (SetAccessControlList::Adds(ids), false) => {
-                bot.information.deputy_admins.extend(ids);
+                plugin.access_list.friends.extend(ids);
             }
             // 从名单中移除一个用户
             (SetAccessControlList::Remove(id), false) => {
@@ -191,16 +350,108 @@ impl RuntimeBot {
             }
             // 从名单中移除多个用户
             (SetAccessControlList::Removes(ids), false) => {
-                bot.information.deputy_admins.retain(|&x| !ids.contains(&x));
+                plugin.access_list.friends.retain(|x| !ids.contains(x));
             }
             // 替换名单为新的用户列表
             (SetAccessControlList::Changes(ids), false) => {
                 plugin.access_list.friends = ids.into_iter().collect();
             }
         }
+        bot.publish_snapshot();
 
         Ok(())
     }
+
+    /// 修改某一插件名单中的规则，见 `AccessRule`
+    ///
+    /// # error
+    ///
+    /// 如果寻找不到插件，会返回Err `BotError::PluginNotFound`
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回Err `BotError::RefExpired` 。
+    /// 这通常出现在 `Bot` 已经关闭，可有个不受 Kovi 管理的线程仍然拥有此 `RuntimeBot`。
+    pub fn set_plugin_access_rules<T: AsRef<str>>(
+        &self,
+        plugin_name: T,
+        change: SetAccessRule,
+    ) -> Result<(), BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let mut bot = bot.write();
+
+        let plugin_name = plugin_name.as_ref();
+
+        let plugin = match bot.plugins.get_mut(plugin_name) {
+            Some(v) => v,
+            None => return Err(BotError::PluginNotFound(plugin_name.to_string())),
+        };
+
+        apply_access_rules(&mut plugin.access_list.rules, change);
+        bot.publish_snapshot();
+
+        Ok(())
+    }
+
+    /// 修改Bot的全局黑名单，命中的事件不会交给任何插件
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use kovi::RuntimeBot;
+    /// use kovi::error::BotError;
+    /// use kovi::plugin::{AccessRule, SetAccessRule};
+    /// use std::time::Duration;
+    ///
+    /// async fn mute(bot: &RuntimeBot) -> Result<(), BotError> {
+    ///     // 禁言此用户两小时
+    ///     bot.set_global_blacklist(SetAccessRule::Add(
+    ///         AccessRule::user(123456).expires_in(Duration::from_secs(2 * 60 * 60)),
+    ///     ))?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # error
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回Err `BotError::RefExpired` 。
+    /// 这通常出现在 `Bot` 已经关闭，可有个不受 Kovi 管理的线程仍然拥有此 `RuntimeBot`。
+    pub fn set_global_blacklist(&self, change: SetAccessRule) -> Result<(), BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let mut bot = bot.write();
+        apply_access_rules(&mut bot.blacklist, change);
+        bot.publish_snapshot();
+
+        Ok(())
+    }
+
+    /// 获取Bot的全局黑名单，不包含已经过期的规则
+    ///
+    /// # error
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回Err `BotError::RefExpired` 。
+    /// 这通常出现在 `Bot` 已经关闭，可有个不受 Kovi 管理的线程仍然拥有此 `RuntimeBot`。
+    pub fn get_global_blacklist(&self) -> Result<Vec<AccessRule>, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let now = chrono::Local::now().timestamp();
+        let rules = bot
+            .read()
+            .blacklist
+            .iter()
+            .filter(|rule| !rule.is_expired(now))
+            .cloned()
+            .collect();
+        Ok(rules)
+    }
 }
 
 /// 管理员控制
@@ -235,6 +486,7 @@ impl RuntimeBot {
                 bot.information.deputy_admins = ids.into_iter().collect();
             }
         }
+        bot.publish_snapshot();
 
         Ok(())
     }
@@ -295,6 +547,79 @@ impl RuntimeBot {
     }
 }
 
+/// 自定义身份
+impl RuntimeBot {
+    /// 授予用户自定义身份，`groups` 为生效的群，为空时在所有群与私聊中生效
+    ///
+    /// 用户已经拥有此身份时，会合并生效的群。
+    ///
+    /// # Error
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn grant_role<T: AsRef<str>>(
+        &self,
+        role: T,
+        user_id: i64,
+        groups: Vec<i64>,
+    ) -> Result<(), BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let mut bot = bot.write();
+        bot.information.roles.grant(role.as_ref(), user_id, groups);
+        bot.publish_snapshot();
+
+        Ok(())
+    }
+
+    /// 撤销用户在这些群内的自定义身份，`groups` 为空时撤销所有
+    ///
+    /// 在所有群生效的身份，只能通过传入空的 `groups` 撤销。
+    ///
+    /// # Error
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn revoke_role<T: AsRef<str>>(
+        &self,
+        role: T,
+        user_id: i64,
+        groups: Vec<i64>,
+    ) -> Result<(), BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let mut bot = bot.write();
+        bot.information
+            .roles
+            .revoke(role.as_ref(), user_id, &groups);
+        bot.publish_snapshot();
+
+        Ok(())
+    }
+
+    /// 获取Bot的所有自定义身份
+    ///
+    /// # Error
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn get_roles(&self) -> Result<Roles, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let roles = bot.read().information.roles.clone();
+        Ok(roles)
+    }
+}
+
 /// 工具
 impl RuntimeBot {
     /// 获取插件自己的路径
@@ -311,6 +636,37 @@ impl RuntimeBot {
             .expect("Get current_exe parent directory failed");
         current_dir.join("data").join(&self.plugin_name)
     }
+
+    /// 获取发送本地文件时的编码方式，用于 `MediaBuilder::build()`
+    ///
+    /// # Error
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn get_media_config(&self) -> Result<MediaConfig, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let config = bot.read().information.media.clone();
+        Ok(config)
+    }
+
+    /// 获取过长消息的拆分配置
+    ///
+    /// # Error
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    pub fn get_split_config(&self) -> Result<SplitConfig, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let config = bot.read().information.split.clone();
+        Ok(config)
+    }
 }
 
 /// 插件控制
@@ -337,12 +693,15 @@ impl RuntimeBot {
                 version: plugin.version.clone(),
                 enabled: *plugin.enabled.borrow(),
                 enable_on_startup: plugin.enable_on_startup,
+                disabled_in: plugin.disabled_in.clone(),
+                meta: (*plugin.meta).clone(),
                 #[cfg(feature = "plugin-access-control")]
                 access_control: plugin.access_control,
                 #[cfg(feature = "plugin-access-control")]
                 list_mode: plugin.list_mode,
                 #[cfg(feature = "plugin-access-control")]
                 access_list: plugin.access_list.clone(),
+                stats: plugin.health.stats(),
             })
             .collect();
 
@@ -442,6 +801,132 @@ impl RuntimeBot {
         let bool_ = *bot_plugin.enabled.borrow();
         Ok(bool_)
     }
+
+    /// 插件在此群是否开启，插件全局关闭时也返回 `false`
+    ///
+    /// # error
+    ///
+    /// 如果寻找不到插件，会返回Err `BotError::PluginNotFound`
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回Err `BotError::RefExpired` 。
+    /// 这通常出现在 `Bot` 已经关闭，可有个不受 Kovi 管理的线程仍然拥有此 `RuntimeBot`。
+    pub fn is_plugin_enable_in<T: AsRef<str>>(
+        &self,
+        plugin_name: T,
+        group_id: i64,
+    ) -> Result<bool, BotError> {
+        self.is_plugin_enable_where(plugin_name, Some(group_id), None)
+    }
+
+    /// 插件对此用户是否开启，插件全局关闭时也返回 `false`
+    ///
+    /// # error
+    ///
+    /// 如果寻找不到插件，会返回Err `BotError::PluginNotFound`
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回Err `BotError::RefExpired` 。
+    /// 这通常出现在 `Bot` 已经关闭，可有个不受 Kovi 管理的线程仍然拥有此 `RuntimeBot`。
+    pub fn is_plugin_enable_for<T: AsRef<str>>(
+        &self,
+        plugin_name: T,
+        user_id: i64,
+    ) -> Result<bool, BotError> {
+        self.is_plugin_enable_where(plugin_name, None, Some(user_id))
+    }
+
+    /// 在此群开启或关闭插件，不影响插件在其他群的状态
+    ///
+    /// 插件全局关闭时，在此群开启也不会运行。
+    ///
+    /// # error
+    ///
+    /// 如果寻找不到插件，会返回Err `BotError::PluginNotFound`
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回Err `BotError::RefExpired` 。
+    /// 这通常出现在 `Bot` 已经关闭，可有个不受 Kovi 管理的线程仍然拥有此 `RuntimeBot`。
+    pub fn set_plugin_enable_in<T: AsRef<str>>(
+        &self,
+        plugin_name: T,
+        group_id: i64,
+        enabled: bool,
+    ) -> Result<(), BotError> {
+        self.set_plugin_enable_where(plugin_name, |disabled_in| {
+            set_contains(&mut disabled_in.groups, group_id, !enabled)
+        })
+    }
+
+    /// 对此用户开启或关闭插件，不影响插件对其他用户的状态
+    ///
+    /// 插件全局关闭时，对此用户开启也不会运行。
+    ///
+    /// # error
+    ///
+    /// 如果寻找不到插件，会返回Err `BotError::PluginNotFound`
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回Err `BotError::RefExpired` 。
+    /// 这通常出现在 `Bot` 已经关闭，可有个不受 Kovi 管理的线程仍然拥有此 `RuntimeBot`。
+    pub fn set_plugin_enable_for<T: AsRef<str>>(
+        &self,
+        plugin_name: T,
+        user_id: i64,
+        enabled: bool,
+    ) -> Result<(), BotError> {
+        self.set_plugin_enable_where(plugin_name, |disabled_in| {
+            set_contains(&mut disabled_in.users, user_id, !enabled)
+        })
+    }
+
+    fn is_plugin_enable_where<T: AsRef<str>>(
+        &self,
+        plugin_name: T,
+        group_id: Option<i64>,
+        user_id: Option<i64>,
+    ) -> Result<bool, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let bot = bot.read();
+        let plugin_name = plugin_name.as_ref();
+
+        let bot_plugin = match bot.plugins.get(plugin_name) {
+            Some(v) => v,
+            None => return Err(BotError::PluginNotFound(plugin_name.to_string())),
+        };
+        Ok(*bot_plugin.enabled.borrow() && bot_plugin.disabled_in.allows(group_id, user_id))
+    }
+
+    fn set_plugin_enable_where<T: AsRef<str>>(
+        &self,
+        plugin_name: T,
+        change: impl FnOnce(&mut PluginDisabledIn),
+    ) -> Result<(), BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let mut bot = bot.write();
+        let plugin_name = plugin_name.as_ref();
+
+        let bot_plugin = match bot.plugins.get_mut(plugin_name) {
+            Some(v) => v,
+            None => return Err(BotError::PluginNotFound(plugin_name.to_string())),
+        };
+        change(&mut bot_plugin.disabled_in);
+        bot.publish_snapshot();
+
+        Ok(())
+    }
+}
+
+fn set_contains(set: &mut ahash::HashSet<i64>, id: i64, contains: bool) {
+    if contains {
+        set.insert(id);
+    } else {
+        set.remove(&id);
+    }
 }
 
 pub(crate) fn disable_plugin<T: AsRef<str>>(
@@ -459,6 +944,7 @@ pub(crate) fn disable_plugin<T: AsRef<str>>(
             None => return Err(BotError::PluginNotFound(plugin_name.to_string())),
         };
         join = bot_plugin.shutdown();
+        bot.publish_snapshot();
     }
 
     Ok(join)
@@ -496,3 +982,43 @@ fn enable_plugin<T: AsRef<str>>(
 
     Ok(())
 }
+
+#[cfg(feature = "plugin-access-control")]
+#[test]
+fn user_access_list_changes_only_touch_the_plugin() {
+    use crate::bot::{Host, KoviConf, Server};
+    use crate::plugin::Plugin;
+
+    let host = Host::IpAddr([127, 0, 0, 1].into());
+    let conf = KoviConf::new(
+        1,
+        Some(vec![5]),
+        Server::new(host.clone(), 8081, String::new(), false),
+        false,
+    );
+    let mut bot = Bot::build(&conf);
+    bot.mount_plugin(Plugin::new("p", "0.0.1", Arc::new(|| Box::pin(async {}))));
+    let bot = Arc::new(RwLock::new(bot));
+    let (api_tx, _api_rx) = mpsc::channel(1);
+    let runtime_bot = RuntimeBot {
+        host,
+        port: 8081,
+        bot: Arc::downgrade(&bot),
+        plugin_name: "p".to_string(),
+        api_tx,
+    };
+
+    runtime_bot
+        .set_plugin_access_control_list("p", false, SetAccessControlList::Adds(vec![2, 3]))
+        .expect("plugin exists");
+    runtime_bot
+        .set_plugin_access_control_list("p", false, SetAccessControlList::Removes(vec![2]))
+        .expect("plugin exists");
+
+    let bot = bot.read();
+    let friends = &bot.plugins["p"].access_list.friends;
+    assert!(friends.contains(&3));
+    assert!(!friends.contains(&2));
+    assert_eq!(bot.information.deputy_admins.len(), 1);
+    assert!(bot.information.deputy_admins.contains(&5));
+}
@@ -1,8 +1,9 @@
 use super::{
-    RuntimeBot, send_api_await_response, send_api_request, send_api_request_with_forget,
+    MsgTarget, RuntimeBot, send_api_await_response, send_api_request, send_api_request_with_forget,
     send_api_request_with_response,
 };
 use crate::bot::ApiReturn;
+use crate::bot::message::{ForwardMessage, ForwardMsgReturn};
 use crate::bot::runtimebot::CanSendApi;
 use crate::bot::{SendApi, message::Message};
 use log::info;
@@ -230,6 +231,86 @@ impl RuntimeBot {
             }
         }
     }
+
+    /// 发送群合并转发消息，并返回消息ID与合并转发ID
+    pub fn send_group_forward_msg(
+        &self,
+        group_id: i64,
+        msg: ForwardMessage,
+    ) -> impl std::future::Future<Output = Result<ForwardMsgReturn, ApiReturn>> {
+        info!("[send] [to group {group_id}]: [forward x{}]", msg.len());
+
+        let send_api = SendApi::new(
+            "send_group_forward_msg",
+            json!({
+                "group_id":group_id,
+                "messages":Message::from(msg),
+            }),
+        );
+
+        let api_rx = send_api_request(&self.api_tx, send_api);
+
+        async move {
+            let r = send_api_await_response(api_rx).await;
+            match r {
+                Ok(v) => ForwardMsgReturn::from_data(&v.data).ok_or(v),
+                Err(v) => Err(v),
+            }
+        }
+    }
+
+    /// 发送私聊合并转发消息，并返回消息ID与合并转发ID
+    pub fn send_private_forward_msg(
+        &self,
+        user_id: i64,
+        msg: ForwardMessage,
+    ) -> impl std::future::Future<Output = Result<ForwardMsgReturn, ApiReturn>> {
+        info!("[send] [to private {user_id}]: [forward x{}]", msg.len());
+
+        let send_api = SendApi::new(
+            "send_private_forward_msg",
+            json!({
+                "user_id":user_id,
+                "messages":Message::from(msg),
+            }),
+        );
+
+        let api_rx = send_api_request(&self.api_tx, send_api);
+
+        async move {
+            let r = send_api_await_response(api_rx).await;
+            match r {
+                Ok(v) => ForwardMsgReturn::from_data(&v.data).ok_or(v),
+                Err(v) => Err(v),
+            }
+        }
+    }
+
+    /// 获取合并转发消息，并解析为 `ForwardMessage`
+    /// # Arguments
+    ///
+    /// `id`: 合并转发 ID
+    pub fn get_forward_msg_return(
+        &self,
+        id: &str,
+    ) -> impl std::future::Future<Output = Result<ForwardMessage, ApiReturn>> {
+        let send_api = SendApi::new(
+            "get_forward_msg",
+            json!({
+                "id":id
+            }),
+        );
+
+        let api_rx = send_api_request(&self.api_tx, send_api);
+
+        async move {
+            let r = send_api_await_response(api_rx).await;
+            match r {
+                Ok(v) => ForwardMessage::from_forward_data(&v.data).ok_or(v),
+                Err(v) => Err(v),
+            }
+        }
+    }
 }
 
 // 这些都是无需处理返回值的api
@@ -251,9 +332,8 @@ impl RuntimeBot {
             }),
         );
         let msg = Message::from(msg);
-        let group_id = &group_id;
         info!("[send] [to group {group_id}]: {}", msg.to_human_string());
-        send_api_request_with_forget(&self.api_tx, send_api);
+        self.send_msg_queued(MsgTarget::Group(group_id), &msg, send_api);
     }
 
     #[cfg(feature = "cqstring")]
@@ -272,13 +352,9 @@ impl RuntimeBot {
                     "auto_escape":true,
             }),
         );
-        let msg = CQMessage::from(msg);
-        let group_id = &group_id;
-        info!(
-            "[send] [to group {group_id}]: {}",
-            Message::from(msg).to_human_string()
-        );
-        send_api_request_with_forget(&self.api_tx, send_api);
+        let msg = Message::from(CQMessage::from(msg));
+        info!("[send] [to group {group_id}]: {}", msg.to_human_string());
+        self.send_msg_queued(MsgTarget::Group(group_id), &msg, send_api);
     }
 
     #[cfg(not(feature = "cqstring"))]
@@ -299,9 +375,8 @@ impl RuntimeBot {
         );
 
         let msg = Message::from(msg);
-        let user_id = &user_id;
         info!("[send] [to private {user_id}]: {}", msg.to_human_string());
-        send_api_request_with_forget(&self.api_tx, send_api);
+        self.send_msg_queued(MsgTarget::Private(user_id), &msg, send_api);
     }
 
     #[cfg(feature = "cqstring")]
@@ -321,13 +396,9 @@ impl RuntimeBot {
             }),
         );
 
-        let msg = CQMessage::from(msg);
-        let user_id = &user_id;
-        info!(
-            "[send] [to private {user_id}]: {}",
-            Message::from(msg).to_human_string()
-        );
-        send_api_request_with_forget(&self.api_tx, send_api);
+        let msg = Message::from(CQMessage::from(msg));
+        info!("[send] [to private {user_id}]: {}", msg.to_human_string());
+        self.send_msg_queued(MsgTarget::Private(user_id), &msg, send_api);
     }
 
     /// 撤回消息
@@ -678,7 +749,7 @@ impl RuntimeBot {
 
         send_api_request_with_response(&self.api_tx, send_api)
     }
-    /// 获取合并转发消息
+    /// 获取合并转发消息，需要解析好的结果请使用 get_forward_msg_return()
     /// # Arguments
     ///
     /// `id`: 合并转发 ID
@@ -1,10 +1,15 @@
 use thiserror::Error;
 
+/// 之后可能会增加新的错误，匹配时需要 `_` 分支
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum MessageError {
     /// 解析出错
     #[error("Parse error: {0}")]
     ParseError(String),
+    /// 无法读取媒体文件
+    #[error("Failed to read file: {0}")]
+    FileReadError(String),
     // #[error("Error, and no one knows why something went wrong")]
     // UnknownError(),
 }
@@ -7,6 +7,10 @@
 //! 中文文档或更多文档请查看[Github-Kovi](https://github.com/Threkork/Kovi) 和 [Kovi-doc](https://threkork.github.io/kovi-doc/)
 #![deny(clippy::unwrap_used)]
 
+/// 分发性能基准使用的内部接口
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
 /// Everything about bots is inside
 pub mod bot;
 /// 一些错误枚举
@@ -46,6 +50,7 @@ pub use chrono;
 pub use croner;
 pub use futures_util;
 pub use log;
+pub use regex;
 pub use serde_json;
 pub use tokio;
 pub use toml;
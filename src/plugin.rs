@@ -1,17 +1,40 @@
+#[cfg(feature = "admin-plugin")]
+pub mod admin_plugin;
+pub(crate) mod cancel;
+pub(crate) mod health;
+#[cfg(feature = "help-plugin")]
+pub mod help_plugin;
+pub(crate) mod limiter;
 pub mod plugin_builder;
+pub(crate) mod sequential;
 
 use crate::PluginBuilder;
+use crate::bot::Bot;
 use crate::bot::plugin_builder::Listen;
 #[cfg(feature = "plugin-access-control")]
 use crate::bot::runtimebot::kovi_api::AccessList;
+use crate::plugin::health::PluginHealth;
+use crate::plugin::limiter::Limiter;
+use crate::plugin::sequential::Sequencer;
 use crate::types::KoviAsyncFn;
+use ahash::HashSet;
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::watch;
 use tokio::task::JoinHandle;
 
+pub use crate::bot::filter::MsgFilter;
+pub use crate::bot::permission::Permission;
 #[cfg(feature = "plugin-access-control")]
-pub use crate::bot::runtimebot::kovi_api::AccessControlMode;
+pub use crate::bot::runtimebot::kovi_api::{AccessControlMode, AccessRule, SetAccessRule};
+pub use cancel::{CANCEL_GRACE, CancellationToken};
+pub use health::{PanicLimit, PluginPanic, PluginStats};
+pub use limiter::{ConcurrencyLimit, OverflowPolicy};
+pub use plugin_builder::{HandlerResult, ListenOptions};
+pub use sequential::SequentialKey;
 
 use crate::task::TASK_MANAGER;
 
@@ -31,7 +54,20 @@ pub struct Plugin {
     pub name: String,
     pub version: String,
     pub(crate) main: Arc<KoviAsyncFn>,
-    pub(crate) listen: Listen,
+    /// 发布快照时与快照共享，注册监听时才复制
+    pub(crate) listen: Arc<Listen>,
+    /// 监听处理的并发限制，为 `None` 时不限制
+    pub(crate) limiter: Option<Arc<Limiter>>,
+    /// 插件级别的顺序处理
+    pub(crate) sequencer: Option<Arc<Sequencer>>,
+    /// 出错记录
+    pub(crate) health: Arc<PluginHealth>,
+    /// 监听处理的超时时间
+    pub(crate) timeout: Option<Duration>,
+    /// 在哪些群与用户上关闭了插件
+    pub(crate) disabled_in: PluginDisabledIn,
+    /// 插件的说明
+    pub(crate) meta: Arc<PluginMeta>,
 
     #[cfg(feature = "plugin-access-control")]
     pub(crate) access_control: bool,
@@ -52,7 +88,13 @@ impl Plugin {
             name: name.into(),
             version: version.into(),
             main,
-            listen: Listen::default(),
+            listen: Arc::default(),
+            limiter: None,
+            sequencer: None,
+            health: Arc::default(),
+            timeout: None,
+            disabled_in: PluginDisabledIn::default(),
+            meta: Arc::default(),
             #[cfg(feature = "plugin-access-control")]
             access_control: false,
             #[cfg(feature = "plugin-access-control")]
@@ -68,12 +110,13 @@ impl Plugin {
 
         let mut enabled = self.enabled.subscribe();
         let main = self.main.clone();
+        let bot = plugin_builder.bot.clone();
 
         tokio::spawn(async move {
             tokio::select! {
                 _ = PLUGIN_NAME.scope(
                         Arc::new(plugin_name),
-                        PLUGIN_BUILDER.scope(plugin_builder, main()),
+                        PLUGIN_BUILDER.scope(plugin_builder, publish_on_yield(bot, main())),
                 ) =>{}
                 _ = async {
                         loop {
@@ -108,7 +151,7 @@ impl Plugin {
         self.enabled.send_modify(|v| {
             *v = false;
         });
-        self.listen.clear();
+        self.listen = Arc::default();
         tokio::spawn(async move {
             for task in task_vec {
                 let _ = task.await;
@@ -117,9 +160,99 @@ impl Plugin {
     }
 }
 
+/// 运行插件的 `main`，每次 `main` 让出或结束时，发布期间注册的监听
+async fn publish_on_yield(bot: Arc<RwLock<Bot>>, main: impl Future<Output = ()>) {
+    let mut main = std::pin::pin!(main);
+    std::future::poll_fn(|cx| {
+        let poll = main.as_mut().poll(cx);
+        bot.read().publish_snapshot_if_dirty();
+        poll
+    })
+    .await
+}
+
+/// 插件的说明，帮助插件会用它生成帮助
+///
+/// ```no_run
+/// use kovi::PluginBuilder;
+/// use kovi::plugin::PluginMeta;
+///
+/// async fn start() {
+///     PluginBuilder::set_meta(
+///         PluginMeta::new()
+///             .description("打招呼")
+///             .usage("发送 hi，Bot 会回复 hi")
+///             .command("hi", "打招呼"),
+///     );
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PluginMeta {
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub usage: Option<String>,
+    /// 插件处理的命令或触发词
+    pub commands: Vec<PluginCommand>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PluginCommand {
+    pub trigger: String,
+    pub description: String,
+}
+
+impl PluginMeta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn author<S: Into<String>>(mut self, author: S) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn usage<S: Into<String>>(mut self, usage: S) -> Self {
+        self.usage = Some(usage.into());
+        self
+    }
+
+    /// 添加一个命令或触发词
+    pub fn command<T: Into<String>, D: Into<String>>(mut self, trigger: T, description: D) -> Self {
+        self.commands.push(PluginCommand {
+            trigger: trigger.into(),
+            description: description.into(),
+        });
+        self
+    }
+}
+
+/// 插件在单个群或用户上的关闭状态，叠加在全局开关之上
+///
+/// 全局关闭时插件在所有群都不会运行。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PluginDisabledIn {
+    pub groups: HashSet<i64>,
+    pub users: HashSet<i64>,
+}
+
+impl PluginDisabledIn {
+    /// 事件所在的群与触发事件的用户都没有关闭插件
+    pub fn allows(&self, group_id: Option<i64>, user_id: Option<i64>) -> bool {
+        !group_id.is_some_and(|group_id| self.groups.contains(&group_id))
+            && !user_id.is_some_and(|user_id| self.users.contains(&user_id))
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub(crate) struct PluginStatus {
     pub(crate) enable_on_startup: bool,
+    #[serde(default)]
+    pub(crate) disabled_in: PluginDisabledIn,
     #[cfg(feature = "plugin-access-control")]
     pub(crate) access_control: bool,
     #[cfg(feature = "plugin-access-control")]
@@ -128,6 +261,41 @@ pub(crate) struct PluginStatus {
     pub(crate) access_list: AccessList,
 }
 
+/// `kovi.plugin.toml` 的内容
+///
+/// 插件位于 `[plugins]` 下，不会与其他的键重名
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct PluginStatusFile {
+    /// Bot的全局黑名单
+    #[cfg(feature = "plugin-access-control")]
+    #[serde(default)]
+    pub(crate) global_blacklist: Vec<AccessRule>,
+    #[serde(default)]
+    pub(crate) plugins: HashMap<String, PluginStatus>,
+}
+
+impl PluginStatusFile {
+    /// 解析 `kovi.plugin.toml`，兼容插件直接位于顶层的旧格式
+    pub(crate) fn from_toml(content: &str) -> Result<Self, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(content)?;
+        let is_new = table
+            .get("plugins")
+            .and_then(toml::Value::as_table)
+            .is_some_and(|plugins| !plugins.contains_key("enable_on_startup"));
+        if !is_new {
+            // 旧格式中除了全局黑名单，顶层的表都是插件
+            let plugins = table
+                .iter()
+                .filter(|(name, value)| *name != "global_blacklist" || value.is_table())
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<toml::Table>();
+            table.retain(|name, value| name == "global_blacklist" && !value.is_table());
+            table.insert("plugins".to_string(), toml::Value::Table(plugins));
+        }
+        toml::Value::Table(table).try_into()
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PluginInfo {
     pub name: String,
@@ -136,6 +304,10 @@ pub struct PluginInfo {
     pub enabled: bool,
     /// 插件是否在Bot启动时启用
     pub enable_on_startup: bool,
+    /// 插件在哪些群与用户上被关闭
+    pub disabled_in: PluginDisabledIn,
+    /// 插件的说明
+    pub meta: PluginMeta,
     /// 插件是否启用框架级访问控制
     #[cfg(feature = "plugin-access-control")]
     pub access_control: bool,
@@ -145,4 +317,172 @@ pub struct PluginInfo {
     /// 插件的访问控制列表
     #[cfg(feature = "plugin-access-control")]
     pub access_list: AccessList,
+    /// 插件运行中出错的统计
+    pub stats: PluginStats,
+}
+
+impl PluginInfo {
+    /// 插件是否会处理此群或此用户的事件，考虑插件的开关与黑白名单
+    pub fn is_available_in(&self, group_id: Option<i64>, user_id: Option<i64>) -> bool {
+        if !self.enabled || !self.disabled_in.allows(group_id, user_id) {
+            return false;
+        }
+        #[cfg(feature = "plugin-access-control")]
+        if self.access_control {
+            let now = chrono::Local::now().timestamp();
+            return self
+                .access_list
+                .allows(self.list_mode, group_id, user_id, now);
+        }
+        true
+    }
+}
+
+#[cfg(feature = "plugin-access-control")]
+#[test]
+fn plugin_status_file_round_trip() {
+    use crate::bot::runtimebot::kovi_api::AccessRule;
+
+    let mut access_list = AccessList::default();
+    access_list.groups.insert(1);
+    access_list
+        .rules
+        .push(AccessRule::users_in_group(1, vec![2]).expires_at(100));
+    let mut file = PluginStatusFile {
+        global_blacklist: vec![AccessRule::user(3)],
+        plugins: HashMap::new(),
+    };
+    file.plugins.insert("hi".to_string(), PluginStatus {
+        enable_on_startup: true,
+        disabled_in: PluginDisabledIn::default(),
+        access_control: true,
+        list_mode: AccessControlMode::WhiteList,
+        access_list,
+    });
+
+    let file = toml::to_string(&file).expect("status serializes");
+    let file = PluginStatusFile::from_toml(&file).expect("valid toml");
+    assert_eq!(file.global_blacklist, vec![AccessRule::user(3)]);
+    assert_eq!(file.plugins["hi"].access_list.rules[0].users, vec![2]);
+
+    // 旧版本的文件
+    let old = "[hi]\nenable_on_startup = false\naccess_control = false\nlist_mode = \"BlackList\"\n\n[hi.access_list]\nfriends = []\ngroups = [1]\n";
+    let file = PluginStatusFile::from_toml(old).expect("valid toml");
+    assert!(file.global_blacklist.is_empty());
+    assert!(!file.plugins["hi"].enable_on_startup);
+}
+
+#[test]
+fn plugin_status_file_keeps_plugins_apart_from_blacklist() {
+    // 插件名与全局黑名单的键相同
+    let new = r#"
+global_blacklist = [{ users = [3] }]
+
+[plugins.global_blacklist]
+enable_on_startup = false
+access_control = false
+list_mode = "BlackList"
+access_list = { friends = [], groups = [] }
+"#;
+    // 旧格式
+    let old = r#"
+global_blacklist = [{ users = [3] }]
+
+[hi]
+enable_on_startup = false
+access_control = false
+list_mode = "BlackList"
+access_list = { friends = [], groups = [] }
+"#;
+
+    for (content, name) in [(new, "global_blacklist"), (old, "hi")] {
+        let file = PluginStatusFile::from_toml(content).expect("valid toml");
+        assert_eq!(file.plugins.len(), 1);
+        assert!(!file.plugins[name].enable_on_startup);
+        #[cfg(feature = "plugin-access-control")]
+        assert_eq!(file.global_blacklist[0].users, vec![3]);
+    }
+}
+
+#[test]
+fn plugin_disabled_in_group_or_user() {
+    let mut disabled_in = PluginDisabledIn::default();
+    disabled_in.groups.insert(1);
+    disabled_in.users.insert(2);
+
+    assert!(!disabled_in.allows(Some(1), Some(3)));
+    assert!(!disabled_in.allows(Some(4), Some(2)));
+    assert!(!disabled_in.allows(None, Some(2)));
+    assert!(disabled_in.allows(Some(4), Some(3)));
+    assert!(disabled_in.allows(None, None));
+}
+
+#[test]
+fn plugin_main_publishes_listeners_once_per_yield() {
+    use crate::MsgEvent;
+    use crate::bot::{Host, KoviConf, Server};
+    use std::sync::atomic::Ordering;
+
+    let host = Host::IpAddr([127, 0, 0, 1].into());
+    let conf = KoviConf::new(
+        1,
+        None,
+        Server::new(host.clone(), 8081, String::new(), false),
+        false,
+    );
+    let mut bot = Bot::build(&conf);
+    let (yielded_tx, yielded_rx) = std::sync::mpsc::channel();
+    let (resume_tx, resume_rx) = tokio::sync::oneshot::channel::<()>();
+    let resume_rx = Arc::new(parking_lot::Mutex::new(Some(resume_rx)));
+    bot.mount_plugin(Plugin::new(
+        "p",
+        "0.0.1",
+        Arc::new(move || {
+            let yielded_tx = yielded_tx.clone();
+            let resume_rx = resume_rx.lock().take();
+            Box::pin(async move {
+                for _ in 0..3 {
+                    PluginBuilder::on_msg(|_: Arc<MsgEvent>| async {});
+                }
+                let _ = yielded_tx.send(());
+                if let Some(resume_rx) = resume_rx {
+                    let _ = resume_rx.await;
+                }
+                PluginBuilder::on_msg(|_: Arc<MsgEvent>| async {});
+            })
+        }),
+    ));
+    let bot = Arc::new(RwLock::new(bot));
+    let snapshot = bot.read().snapshot.clone();
+    let listeners = || {
+        snapshot
+            .load()
+            .plugins
+            .iter()
+            .map(|(_, plugin)| plugin.listen.list.len())
+            .sum::<usize>()
+    };
+
+    let (api_tx, _api_rx) = tokio::sync::mpsc::channel(1);
+    let _guard = crate::RT.enter();
+    let builder = PluginBuilder::new("p".to_string(), bot.clone(), host, 8081, api_tx);
+    bot.read().plugins["p"].run(builder);
+
+    // 让出时一起发布前三个监听
+    yielded_rx.recv().expect("main started");
+    while bot.read().snapshot_dirty.load(Ordering::Acquire) {
+        std::thread::yield_now();
+    }
+    assert_eq!(listeners(), 3);
+
+    resume_tx.send(()).expect("main waiting");
+    while listeners() < 4 {
+        std::thread::yield_now();
+    }
+    // 快照与插件表共享同一份监听
+    let bot = bot.read();
+    assert!(Arc::ptr_eq(
+        &bot.plugins["p"].listen,
+        &snapshot.load().plugins[0].1.listen
+    ));
 }
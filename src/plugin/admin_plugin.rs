@@ -0,0 +1,417 @@
+use crate::PluginBuilder;
+use crate::bot::runtimebot::RuntimeBot;
+use crate::bot::runtimebot::kovi_api::SetAdmin;
+#[cfg(feature = "plugin-access-control")]
+use crate::bot::runtimebot::kovi_api::{AccessControlMode, SetAccessControlList};
+use crate::event::AdminMsgEvent;
+use crate::plugin::{Plugin, PluginMeta};
+use crate::utils::fill_placeholders;
+use std::sync::Arc;
+
+/// 内置管理插件的名称
+pub const ADMIN_PLUGIN_NAME: &str = "kovi-admin";
+
+/// 内置的管理插件，Bot 的管理员可以通过聊天命令管理插件、管理员与名单
+///
+/// ```no_run
+/// use kovi::build_bot;
+/// use kovi::plugin::admin_plugin::AdminPlugin;
+///
+/// let mut bot = build_bot!();
+/// bot.mount_plugin(AdminPlugin::new().prefixes(["/kovi", "/bot"]).build());
+/// bot.run();
+/// ```
+///
+/// 命令，以 `/kovi` 为例：
+///
+/// - `/kovi list` 列出插件
+/// - `/kovi enable <插件>`、`/kovi disable <插件>`、`/kovi restart <插件>`
+/// - `/kovi admin list`、`/kovi admin add <QQ>`、`/kovi admin remove <QQ>`，增删管理员只有主管理员可以使用
+/// - `/kovi access <插件> on|off|white|black`
+/// - `/kovi access <插件> add|remove group|friend <号码>`
+#[derive(Clone, Debug)]
+pub struct AdminPlugin {
+    prefixes: Vec<String>,
+    replies: AdminReplies,
+}
+
+impl Default for AdminPlugin {
+    fn default() -> Self {
+        AdminPlugin {
+            prefixes: vec!["/kovi".to_string()],
+            replies: AdminReplies::default(),
+        }
+    }
+}
+
+impl AdminPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 命令的前缀，默认为 `/kovi`
+    pub fn prefixes<I, S>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.prefixes = prefixes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 回复的文本，默认为中文，见 `AdminReplies::en()`
+    pub fn replies(mut self, replies: AdminReplies) -> Self {
+        self.replies = replies;
+        self
+    }
+
+    /// 生成插件，通过 `Bot::mount_plugin()` 挂载
+    pub fn build(self) -> Plugin {
+        let config = Arc::new(self);
+        Plugin::new(
+            ADMIN_PLUGIN_NAME,
+            env!("CARGO_PKG_VERSION"),
+            Arc::new(move || {
+                let config = config.clone();
+                Box::pin(async move {
+                    let mut meta = PluginMeta::new().description(&config.replies.description);
+                    for prefix in &config.prefixes {
+                        meta = meta.command(prefix, &config.replies.command_description);
+                    }
+                    PluginBuilder::set_meta(meta);
+                    let bot = PluginBuilder::get_runtime_bot();
+                    PluginBuilder::on_admin_msg(move |event| {
+                        let config = config.clone();
+                        let bot = bot.clone();
+                        async move {
+                            let Some(command) = parse_command(
+                                event.borrow_text().unwrap_or_default(),
+                                &config.prefixes,
+                            ) else {
+                                return;
+                            };
+                            let reply = config.execute(&bot, &event, command).await;
+                            event.reply(reply);
+                        }
+                    });
+                })
+            }),
+        )
+    }
+
+    async fn execute(
+        &self,
+        bot: &RuntimeBot,
+        event: &AdminMsgEvent,
+        command: AdminCommand<'_>,
+    ) -> String {
+        let r = &self.replies;
+        let result = match command {
+            AdminCommand::Help => Ok(r.usage.clone()),
+            AdminCommand::Unknown => Ok(r.unknown_command.clone()),
+            AdminCommand::List => bot.get_plugin_info().map(|mut plugins| {
+                plugins.sort_by(|a, b| a.name.cmp(&b.name));
+                let mut reply = r.plugin_list.clone();
+                for plugin in plugins {
+                    let status = if plugin.enabled {
+                        &r.status_enabled
+                    } else {
+                        &r.status_disabled
+                    };
+                    reply.push('\n');
+                    reply.push_str(&fill_placeholders(
+                        &r.plugin_line,
+                        &[
+                            ("plugin", &plugin.name),
+                            ("version", &plugin.version),
+                            ("status", status),
+                        ],
+                    ));
+                }
+                reply
+            }),
+            AdminCommand::Enable(name) => bot
+                .enable_plugin(name)
+                .map(|_| fill_placeholders(&r.enabled, &[("plugin", name)])),
+            AdminCommand::Disable(name) | AdminCommand::Restart(name)
+                if name == ADMIN_PLUGIN_NAME =>
+            {
+                Ok(r.cannot_stop_self.clone())
+            }
+            AdminCommand::Disable(name) => bot
+                .disable_plugin(name)
+                .map(|_| fill_placeholders(&r.disabled, &[("plugin", name)])),
+            AdminCommand::Restart(name) => bot
+                .restart_plugin(name)
+                .await
+                .map(|_| fill_placeholders(&r.restarted, &[("plugin", name)])),
+            AdminCommand::AdminList => bot.get_main_admin().and_then(|main| {
+                let mut admins = bot.get_deputy_admins()?;
+                admins.sort();
+                let admins: Vec<String> = admins.iter().map(i64::to_string).collect();
+                Ok(fill_placeholders(
+                    &r.admin_list,
+                    &[("main", &main.to_string()), ("admins", &admins.join(", "))],
+                ))
+            }),
+            AdminCommand::AdminAdd(_) | AdminCommand::AdminRemove(_)
+                if bot.get_main_admin().ok() != Some(event.user_id) =>
+            {
+                Ok(r.main_admin_only.clone())
+            }
+            AdminCommand::AdminAdd(id) => bot
+                .set_deputy_admins(SetAdmin::Add(id))
+                .map(|_| fill_placeholders(&r.admin_added, &[("id", &id.to_string())])),
+            AdminCommand::AdminRemove(id) => bot
+                .set_deputy_admins(SetAdmin::Remove(id))
+                .map(|_| fill_placeholders(&r.admin_removed, &[("id", &id.to_string())])),
+            #[cfg(feature = "plugin-access-control")]
+            AdminCommand::Access { plugin, change } => {
+                let result = match change {
+                    AccessChange::Enable(enable) => bot.set_plugin_access_control(plugin, enable),
+                    AccessChange::Mode(mode) => bot.set_plugin_access_control_mode(plugin, mode),
+                    AccessChange::Add { is_group, id } => bot.set_plugin_access_control_list(
+                        plugin,
+                        is_group,
+                        SetAccessControlList::Add(id),
+                    ),
+                    AccessChange::Remove { is_group, id } => bot.set_plugin_access_control_list(
+                        plugin,
+                        is_group,
+                        SetAccessControlList::Remove(id),
+                    ),
+                };
+                result.map(|_| fill_placeholders(&r.access_changed, &[("plugin", plugin)]))
+            }
+        };
+
+        match result {
+            Ok(reply) => reply,
+            Err(e) => fill_placeholders(&r.error, &[("error", &e.to_string())]),
+        }
+    }
+}
+
+/// 管理插件的回复文本
+///
+/// 文本中的 `{plugin}`、`{id}` 等占位符会被替换。
+#[derive(Clone, Debug)]
+pub struct AdminReplies {
+    /// 帮助中插件的说明
+    pub description: String,
+    /// 帮助中命令前缀的说明
+    pub command_description: String,
+    pub usage: String,
+    pub unknown_command: String,
+    /// 插件列表的标题
+    pub plugin_list: String,
+    /// 插件列表的每一行，可用 `{plugin}`、`{version}`、`{status}`
+    pub plugin_line: String,
+    pub status_enabled: String,
+    pub status_disabled: String,
+    /// 可用 `{plugin}`
+    pub enabled: String,
+    /// 可用 `{plugin}`
+    pub disabled: String,
+    /// 可用 `{plugin}`
+    pub restarted: String,
+    /// 试图关闭或重载管理插件本身
+    pub cannot_stop_self: String,
+    /// 可用 `{main}`、`{admins}`
+    pub admin_list: String,
+    /// 可用 `{id}`
+    pub admin_added: String,
+    /// 可用 `{id}`
+    pub admin_removed: String,
+    pub main_admin_only: String,
+    /// 可用 `{plugin}`
+    pub access_changed: String,
+    /// 可用 `{error}`
+    pub error: String,
+}
+
+impl Default for AdminReplies {
+    fn default() -> Self {
+        Self::zh()
+    }
+}
+
+impl AdminReplies {
+    pub fn zh() -> Self {
+        AdminReplies {
+            description: "管理插件、管理员与名单，只有Bot的管理员可以使用".to_string(),
+            command_description: "管理命令，后接 help 查看用法".to_string(),
+            usage: "管理命令：\n\
+                list 列出插件\n\
+                enable|disable|restart <插件> 启用、关闭、重载插件\n\
+                admin list|add|remove <QQ> 管理员\n\
+                access <插件> on|off|white|black 名单开关与模式\n\
+                access <插件> add|remove group|friend <号码> 修改名单"
+                .to_string(),
+            unknown_command: "未知的命令，使用 help 命令查看帮助".to_string(),
+            plugin_list: "插件列表：".to_string(),
+            plugin_line: "{plugin} {version} [{status}]".to_string(),
+            status_enabled: "启用".to_string(),
+            status_disabled: "关闭".to_string(),
+            enabled: "已启用插件 {plugin}".to_string(),
+            disabled: "已关闭插件 {plugin}".to_string(),
+            restarted: "已重载插件 {plugin}".to_string(),
+            cannot_stop_self: "不能关闭或重载管理插件本身".to_string(),
+            admin_list: "主管理员：{main}\n副管理员：{admins}".to_string(),
+            admin_added: "已添加管理员 {id}".to_string(),
+            admin_removed: "已移除管理员 {id}".to_string(),
+            main_admin_only: "只有主管理员可以修改管理员".to_string(),
+            access_changed: "已修改插件 {plugin} 的名单".to_string(),
+            error: "操作失败：{error}".to_string(),
+        }
+    }
+
+    pub fn en() -> Self {
+        AdminReplies {
+            description: "Manage plugins, admins and access lists, for bot admins only".to_string(),
+            command_description: "Admin commands, followed by help for usage".to_string(),
+            usage: "Admin commands:\n\
+                list - list plugins\n\
+                enable|disable|restart <plugin>\n\
+                admin list|add|remove <id>\n\
+                access <plugin> on|off|white|black\n\
+                access <plugin> add|remove group|friend <id>"
+                .to_string(),
+            unknown_command: "Unknown command, use the help command for usage".to_string(),
+            plugin_list: "Plugins:".to_string(),
+            plugin_line: "{plugin} {version} [{status}]".to_string(),
+            status_enabled: "enabled".to_string(),
+            status_disabled: "disabled".to_string(),
+            enabled: "Enabled plugin {plugin}".to_string(),
+            disabled: "Disabled plugin {plugin}".to_string(),
+            restarted: "Restarted plugin {plugin}".to_string(),
+            cannot_stop_self: "The admin plugin cannot disable or restart itself".to_string(),
+            admin_list: "Main admin: {main}\nAdmins: {admins}".to_string(),
+            admin_added: "Added admin {id}".to_string(),
+            admin_removed: "Removed admin {id}".to_string(),
+            main_admin_only: "Only the main admin can change admins".to_string(),
+            access_changed: "Updated the access list of plugin {plugin}".to_string(),
+            error: "Failed: {error}".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum AdminCommand<'a> {
+    Help,
+    Unknown,
+    List,
+    Enable(&'a str),
+    Disable(&'a str),
+    Restart(&'a str),
+    AdminList,
+    AdminAdd(i64),
+    AdminRemove(i64),
+    #[cfg(feature = "plugin-access-control")]
+    Access {
+        plugin: &'a str,
+        change: AccessChange,
+    },
+}
+
+#[cfg(feature = "plugin-access-control")]
+#[derive(Debug, PartialEq)]
+enum AccessChange {
+    Enable(bool),
+    Mode(AccessControlMode),
+    Add { is_group: bool, id: i64 },
+    Remove { is_group: bool, id: i64 },
+}
+
+/// 没有以任一前缀开头时返回 `None`
+fn parse_command<'a>(text: &'a str, prefixes: &[String]) -> Option<AdminCommand<'a>> {
+    let text = text.trim();
+    let rest = prefixes.iter().find_map(|prefix| {
+        let rest = text.strip_prefix(prefix.as_str())?;
+        (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(rest)
+    })?;
+    let args: Vec<&str> = rest.split_whitespace().collect();
+
+    let command = match args.as_slice() {
+        [] | ["help"] => AdminCommand::Help,
+        ["list" | "plugins"] => AdminCommand::List,
+        ["enable", name] => AdminCommand::Enable(name),
+        ["disable", name] => AdminCommand::Disable(name),
+        ["restart", name] => AdminCommand::Restart(name),
+        ["admin"] | ["admin", "list"] => AdminCommand::AdminList,
+        ["admin", "add", id] => id
+            .parse()
+            .map_or(AdminCommand::Unknown, AdminCommand::AdminAdd),
+        ["admin", "remove", id] => id
+            .parse()
+            .map_or(AdminCommand::Unknown, AdminCommand::AdminRemove),
+        #[cfg(feature = "plugin-access-control")]
+        ["access", plugin, args @ ..] => match parse_access_change(args) {
+            Some(change) => AdminCommand::Access { plugin, change },
+            None => AdminCommand::Unknown,
+        },
+        _ => AdminCommand::Unknown,
+    };
+    Some(command)
+}
+
+#[cfg(feature = "plugin-access-control")]
+fn parse_access_change(args: &[&str]) -> Option<AccessChange> {
+    let change = match args {
+        ["on"] => AccessChange::Enable(true),
+        ["off"] => AccessChange::Enable(false),
+        ["white"] => AccessChange::Mode(AccessControlMode::WhiteList),
+        ["black"] => AccessChange::Mode(AccessControlMode::BlackList),
+        [action @ ("add" | "remove"), kind @ ("group" | "friend"), id] => {
+            let is_group = *kind == "group";
+            let id = id.parse().ok()?;
+            if *action == "add" {
+                AccessChange::Add { is_group, id }
+            } else {
+                AccessChange::Remove { is_group, id }
+            }
+        }
+        _ => return None,
+    };
+    Some(change)
+}
+
+#[test]
+fn parse_admin_commands() {
+    let prefixes = vec!["/kovi".to_string()];
+
+    assert_eq!(parse_command("hello", &prefixes), None);
+    assert_eq!(parse_command("/kovifoo", &prefixes), None);
+    assert_eq!(parse_command("/kovi", &prefixes), Some(AdminCommand::Help));
+    assert_eq!(
+        parse_command(" /kovi  disable  hi ", &prefixes),
+        Some(AdminCommand::Disable("hi"))
+    );
+    assert_eq!(
+        parse_command("/kovi admin add 123", &prefixes),
+        Some(AdminCommand::AdminAdd(123))
+    );
+    assert_eq!(
+        parse_command("/kovi admin add abc", &prefixes),
+        Some(AdminCommand::Unknown)
+    );
+    #[cfg(feature = "plugin-access-control")]
+    assert_eq!(
+        parse_command("/kovi access hi add group 10", &prefixes),
+        Some(AdminCommand::Access {
+            plugin: "hi",
+            change: AccessChange::Add {
+                is_group: true,
+                id: 10
+            },
+        })
+    );
+
+    assert_eq!(
+        fill_placeholders(
+            "{plugin} {version}",
+            &[("plugin", "hi"), ("version", "0.1")]
+        ),
+        "hi 0.1"
+    );
+}
@@ -0,0 +1,89 @@
+use tokio::sync::watch;
+
+tokio::task_local! {
+    pub(crate) static CANCEL_TOKEN: CancellationToken;
+}
+
+/// 监听处理的取消信号
+///
+/// 处理超时后会被取消，处理函数可以借此收尾。取消后经过 `CANCEL_GRACE` 仍未结束的处理会被直接中断。
+///
+/// ```no_run
+/// use kovi::plugin::{CancellationToken, ListenOptions};
+/// use kovi::{MsgEvent, PluginBuilder, tokio};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// async fn slow_request() {}
+///
+/// async fn start() {
+///     PluginBuilder::on_with(
+///         ListenOptions::new().timeout(Duration::from_secs(10)),
+///         |event: Arc<MsgEvent>| async move {
+///             let token = CancellationToken::current();
+///             tokio::select! {
+///                 _ = slow_request() => {}
+///                 _ = token.cancelled() => {
+///                     event.reply("请求超时了");
+///                 }
+///             }
+///         },
+///     );
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CancellationToken {
+    rx: Option<watch::Receiver<bool>>,
+}
+
+/// 取消后留给处理函数收尾的时间
+pub const CANCEL_GRACE: std::time::Duration = std::time::Duration::from_secs(3);
+
+impl CancellationToken {
+    pub(crate) fn new() -> (watch::Sender<bool>, Self) {
+        let (tx, rx) = watch::channel(false);
+        (tx, CancellationToken { rx: Some(rx) })
+    }
+
+    /// 获取当前监听处理的取消信号
+    ///
+    /// 在没有设置超时的处理，或者在监听处理之外调用时，返回的信号永远不会被取消。
+    pub fn current() -> Self {
+        CANCEL_TOKEN
+            .try_with(|token| token.clone())
+            .unwrap_or(CancellationToken { rx: None })
+    }
+
+    /// 是否已经被取消
+    pub fn is_cancelled(&self) -> bool {
+        self.rx.as_ref().is_some_and(|rx| *rx.borrow())
+    }
+
+    /// 等待直到被取消
+    pub async fn cancelled(&self) {
+        match &self.rx {
+            Some(rx) => {
+                let mut rx = rx.clone();
+                if rx.wait_for(|cancelled| *cancelled).await.is_err() {
+                    // 处理已经结束，不会再被取消
+                    std::future::pending::<()>().await;
+                }
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[test]
+fn cancellation_token_in_scope() {
+    assert!(!CancellationToken::current().is_cancelled());
+
+    let (tx, token) = CancellationToken::new();
+    crate::RT.block_on(CANCEL_TOKEN.scope(token, async move {
+        let token = CancellationToken::current();
+        assert!(!token.is_cancelled());
+        tx.send(true).expect("receiver alive");
+        token.cancelled().await;
+        assert!(token.is_cancelled());
+    }));
+}
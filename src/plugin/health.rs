@@ -0,0 +1,386 @@
+use crate::Message;
+use crate::bot::SendApi;
+use crate::bot::handler::{InternalInternalEvent, KoviEvent};
+use crate::bot::plugin_builder::event::Event;
+use crate::bot::runtimebot::send_api_request_with_forget;
+use crate::event::{PluginErrorEvent, PluginErrorKind, message_sender};
+use crate::types::ApiAndOneshot;
+use futures_util::FutureExt;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Once};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+
+thread_local! {
+    /// panic hook 捕获的 backtrace，由同一线程上的 `catch_unwind` 取走
+    static PANIC_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// 在原有的 panic hook 之前记录 backtrace
+pub(crate) fn install_panic_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let prev = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let backtrace = Backtrace::capture();
+            if backtrace.status() == BacktraceStatus::Captured {
+                PANIC_BACKTRACE.set(Some(backtrace.to_string()));
+            }
+            prev(info);
+        }));
+    });
+}
+
+/// 插件运行中出错的统计
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PluginStats {
+    /// 处理函数 panic 的次数
+    pub panic_count: u64,
+    /// 最近一次 panic
+    pub last_panic: Option<PluginPanic>,
+    /// 处理函数返回 `Err` 的次数
+    pub error_count: u64,
+    /// 最近一次返回的错误信息
+    pub last_error: Option<String>,
+    /// 处理超时的次数
+    pub timeout_count: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PluginPanic {
+    /// panic 信息
+    pub message: String,
+    /// 需要设置 `RUST_BACKTRACE` 环境变量才会有
+    pub backtrace: Option<String>,
+    /// 发生的时间戳，秒
+    pub time: i64,
+}
+
+/// 在 `window` 时间内 panic 达到 `max_panics` 次时，自动关闭插件
+#[derive(Debug, Clone, Copy)]
+pub struct PanicLimit {
+    pub max_panics: usize,
+    pub window: Duration,
+}
+
+impl PanicLimit {
+    pub fn new(max_panics: usize, window: Duration) -> Self {
+        PanicLimit { max_panics, window }
+    }
+}
+
+/// 插件的出错记录，插件的所有克隆共享同一份
+#[derive(Default)]
+pub(crate) struct PluginHealth {
+    inner: Mutex<HealthInner>,
+}
+
+#[derive(Default)]
+struct HealthInner {
+    stats: PluginStats,
+    recent_panics: VecDeque<Instant>,
+    panic_limit: Option<PanicLimit>,
+    error_reply: Option<String>,
+}
+
+impl PluginHealth {
+    pub(crate) fn stats(&self) -> PluginStats {
+        self.inner.lock().stats.clone()
+    }
+
+    pub(crate) fn set_panic_limit(&self, limit: Option<PanicLimit>) {
+        let mut inner = self.inner.lock();
+        inner.panic_limit = limit;
+        inner.recent_panics.clear();
+    }
+
+    pub(crate) fn set_error_reply(&self, reply: Option<String>) {
+        self.inner.lock().error_reply = reply;
+    }
+
+    /// 记录一次处理函数返回的错误，返回需要回复给用户的消息
+    pub(crate) fn record_error(&self, error: &str) -> Option<String> {
+        let mut inner = self.inner.lock();
+        inner.stats.error_count += 1;
+        inner.stats.last_error = Some(error.to_string());
+        inner
+            .error_reply
+            .as_ref()
+            .map(|reply| reply.replace("{error}", error))
+    }
+
+    pub(crate) fn record_timeout(&self) {
+        self.inner.lock().stats.timeout_count += 1;
+    }
+
+    /// 记录一次 panic，返回是否达到了自动关闭的条件
+    pub(crate) fn record_panic(&self, message: String, backtrace: Option<String>) -> bool {
+        let mut inner = self.inner.lock();
+        inner.stats.panic_count += 1;
+        inner.stats.last_panic = Some(PluginPanic {
+            message,
+            backtrace,
+            time: chrono::Local::now().timestamp(),
+        });
+
+        let Some(limit) = inner.panic_limit else {
+            return false;
+        };
+        let now = Instant::now();
+        inner.recent_panics.push_back(now);
+        while let Some(first) = inner.recent_panics.front()
+            && now.duration_since(*first) > limit.window
+        {
+            inner.recent_panics.pop_front();
+        }
+        if inner.recent_panics.len() >= limit.max_panics.max(1) {
+            inner.recent_panics.clear();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// 出错时上报到事件循环需要的东西
+#[derive(Clone)]
+pub(crate) struct ErrorReporter {
+    pub(crate) plugin_name: Arc<String>,
+    pub(crate) health: Arc<PluginHealth>,
+    pub(crate) event_tx: Option<mpsc::WeakSender<InternalInternalEvent>>,
+    /// 为 `false` 时不发出 `PluginErrorEvent`，用于处理 `PluginErrorEvent` 本身的监听
+    pub(crate) dispatch: bool,
+    /// 用于把错误回复给用户，为 `None` 时不回复
+    pub(crate) api_tx: Option<mpsc::Sender<ApiAndOneshot>>,
+    /// 插件的开关，事件循环不可用时直接用它停止插件
+    pub(crate) enabled: watch::Sender<bool>,
+}
+
+impl ErrorReporter {
+    /// 运行插件的处理，捕获其中的 panic，记录后交给事件循环发出 `PluginErrorEvent`
+    ///
+    /// 发生 panic 时返回 `None`
+    pub(crate) async fn catch_panic<F: Future>(&self, fut: F) -> Option<F::Output> {
+        // 每次 poll 前清掉此线程上别处 panic 留下的 backtrace
+        let mut fut = std::pin::pin!(fut);
+        let fut = std::future::poll_fn(move |cx| {
+            PANIC_BACKTRACE.set(None);
+            fut.as_mut().poll(cx)
+        });
+        let payload = match AssertUnwindSafe(fut).catch_unwind().await {
+            Ok(output) => return Some(output),
+            Err(payload) => payload,
+        };
+
+        let message = panic_message(&*payload);
+        let backtrace = PANIC_BACKTRACE.take();
+        let disabled = self.health.record_panic(message.clone(), backtrace.clone());
+
+        log::error!("Plugin '{}' panicked: {message}", self.plugin_name);
+        if disabled {
+            log::error!(
+                "Plugin '{}' panicked too many times and will be disabled",
+                self.plugin_name
+            );
+        }
+
+        let reported = self
+            .report(PluginErrorEvent {
+                plugin_name: self.plugin_name.to_string(),
+                kind: PluginErrorKind::Panic,
+                message,
+                backtrace,
+                disabled,
+            })
+            .await;
+        if disabled && !reported {
+            // 事件循环不可用，无法完整地关闭插件，只停止插件的监听
+            self.enabled.send_replace(false);
+        }
+        None
+    }
+
+    /// 处理函数返回了错误，记录并按插件的设置回复用户
+    pub(crate) async fn handler_error(&self, event: &dyn Event, type_name: &str, error: String) {
+        log::error!(
+            "Plugin '{}' failed to handle {}: {error}",
+            self.plugin_name,
+            event_summary(event, type_name)
+        );
+
+        if let Some(reply) = self.health.record_error(&error)
+            && let Some(api_tx) = &self.api_tx
+        {
+            reply_to(api_tx, event, reply);
+        }
+
+        self.report(PluginErrorEvent {
+            plugin_name: self.plugin_name.to_string(),
+            kind: PluginErrorKind::Error,
+            message: error,
+            backtrace: None,
+            disabled: false,
+        })
+        .await;
+    }
+
+    /// 处理超时，`finished` 为取消后处理是否在收尾时间内自行结束
+    pub(crate) async fn handler_timeout(
+        &self,
+        event: &dyn Event,
+        type_name: &str,
+        timeout: Duration,
+        finished: bool,
+    ) {
+        let message = if finished {
+            format!("timed out after {timeout:?} and finished after being cancelled")
+        } else {
+            format!("timed out after {timeout:?} and was cut off")
+        };
+        log::warn!(
+            "Plugin '{}' {message} while handling {}",
+            self.plugin_name,
+            event_summary(event, type_name)
+        );
+        self.health.record_timeout();
+
+        self.report(PluginErrorEvent {
+            plugin_name: self.plugin_name.to_string(),
+            kind: PluginErrorKind::Timeout,
+            message,
+            backtrace: None,
+            disabled: false,
+        })
+        .await;
+    }
+
+    /// 交给事件循环处理，返回是否送达
+    async fn report(&self, event: PluginErrorEvent) -> bool {
+        let Some(event_tx) = self.event_tx.as_ref().and_then(|tx| tx.upgrade()) else {
+            return false;
+        };
+        let event = KoviEvent::PluginError {
+            event,
+            dispatch: self.dispatch,
+        };
+        if let Err(e) = event_tx.send(InternalInternalEvent::KoviEvent(event)).await {
+            log::debug!("通道关闭：{e}");
+            return false;
+        }
+        true
+    }
+}
+
+/// 事件的简要描述，例如 `GroupMsgEvent(group: 1, user: 2)`
+fn event_summary(event: &dyn Event, type_name: &str) -> String {
+    match (event.event_group_id(), event.event_user_id()) {
+        (Some(group), Some(user)) => format!("{type_name}(group: {group}, user: {user})"),
+        (Some(group), None) => format!("{type_name}(group: {group})"),
+        (None, Some(user)) => format!("{type_name}(user: {user})"),
+        (None, None) => type_name.to_string(),
+    }
+}
+
+/// 只回复消息事件
+fn reply_to(api_tx: &mpsc::Sender<ApiAndOneshot>, event: &dyn Event, reply: String) {
+    if message_sender(event).is_none() {
+        return;
+    }
+
+    let send_api = match (event.event_group_id(), event.event_user_id()) {
+        (Some(group_id), _) => SendApi::new(
+            "send_msg",
+            json!({
+                "message_type": "group",
+                "group_id": group_id,
+                "message": Message::from(reply),
+            }),
+        ),
+        (None, Some(user_id)) => SendApi::new(
+            "send_msg",
+            json!({
+                "message_type": "private",
+                "user_id": user_id,
+                "message": Message::from(reply),
+            }),
+        ),
+        (None, None) => return,
+    };
+    send_api_request_with_forget(api_tx, send_api);
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+#[test]
+fn catch_panic_records_and_limits() {
+    let health = Arc::new(PluginHealth::default());
+    health.set_panic_limit(Some(PanicLimit::new(2, Duration::from_secs(60))));
+    let reporter = ErrorReporter {
+        plugin_name: Arc::new("test".to_string()),
+        health: health.clone(),
+        event_tx: None,
+        dispatch: true,
+        api_tx: None,
+        enabled: watch::channel(true).0,
+    };
+
+    // 别处的 panic 留下的 backtrace 不会被记到这次
+    PANIC_BACKTRACE.set(Some("stale".to_string()));
+    crate::RT.block_on(reporter.catch_panic(async { panic!("boom") }));
+    let stats = health.stats();
+    assert_eq!(stats.panic_count, 1);
+    let panic = stats.last_panic.expect("panic recorded");
+    assert_eq!(panic.message, "boom");
+    assert_ne!(panic.backtrace.as_deref(), Some("stale"));
+
+    assert!(health.record_panic("again".to_string(), None));
+    assert!(!health.record_panic("reset".to_string(), None));
+
+    assert_eq!(health.record_error("bad input"), None);
+    health.set_error_reply(Some("出错了：{error}".to_string()));
+    assert_eq!(
+        health.record_error("bad input").as_deref(),
+        Some("出错了：bad input")
+    );
+    assert_eq!(health.stats().error_count, 2);
+}
+
+#[test]
+fn catch_panic_stops_plugin_without_event_loop() {
+    let health = Arc::new(PluginHealth::default());
+    health.set_panic_limit(Some(PanicLimit::new(1, Duration::from_secs(60))));
+    let (tx, rx) = mpsc::channel(1);
+    let closed = tx.downgrade();
+    drop((tx, rx));
+
+    for event_tx in [None, Some(closed)] {
+        let enabled = watch::channel(true).0;
+        let reporter = ErrorReporter {
+            plugin_name: Arc::new("test".to_string()),
+            health: health.clone(),
+            event_tx,
+            dispatch: true,
+            api_tx: None,
+            enabled: enabled.clone(),
+        };
+
+        crate::RT.block_on(reporter.catch_panic(async { panic!("boom") }));
+        assert!(!*enabled.borrow());
+    }
+}
@@ -0,0 +1,273 @@
+use crate::PluginBuilder;
+use crate::plugin::{Plugin, PluginInfo, PluginMeta};
+use crate::utils::fill_placeholders;
+use std::sync::Arc;
+
+/// 内置帮助插件的名称
+pub const HELP_PLUGIN_NAME: &str = "kovi-help";
+
+/// 内置的帮助插件，根据插件的 `PluginMeta` 生成帮助
+///
+/// 只列出在当前会话中启用，且没有被名单拦下的插件。
+///
+/// ```no_run
+/// use kovi::build_bot;
+/// use kovi::plugin::help_plugin::HelpPlugin;
+///
+/// let mut bot = build_bot!();
+/// bot.mount_plugin(HelpPlugin::new().build());
+/// bot.run();
+/// ```
+///
+/// 发送 `help` 列出插件，发送 `help <插件>` 查看插件的详情。
+#[derive(Clone, Debug)]
+pub struct HelpPlugin {
+    commands: Vec<String>,
+    replies: HelpReplies,
+}
+
+impl Default for HelpPlugin {
+    fn default() -> Self {
+        HelpPlugin {
+            commands: vec!["help".to_string(), "帮助".to_string()],
+            replies: HelpReplies::default(),
+        }
+    }
+}
+
+impl HelpPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 触发帮助的命令，默认为 `help` 与 `帮助`
+    pub fn commands<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.commands = commands.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 回复的文本，默认为中文，见 `HelpReplies::en()`
+    pub fn replies(mut self, replies: HelpReplies) -> Self {
+        self.replies = replies;
+        self
+    }
+
+    /// 生成插件，通过 `Bot::mount_plugin()` 挂载
+    pub fn build(self) -> Plugin {
+        let config = Arc::new(self);
+        Plugin::new(
+            HELP_PLUGIN_NAME,
+            env!("CARGO_PKG_VERSION"),
+            Arc::new(move || {
+                let config = config.clone();
+                Box::pin(async move {
+                    let bot = PluginBuilder::get_runtime_bot();
+                    PluginBuilder::on_msg(move |event| {
+                        let config = config.clone();
+                        let bot = bot.clone();
+                        async move {
+                            let Some((command, name)) =
+                                config.parse(event.borrow_text().unwrap_or_default())
+                            else {
+                                return;
+                            };
+                            let Ok(plugins) = bot.get_plugin_info() else {
+                                return;
+                            };
+                            let plugins: Vec<PluginInfo> = plugins
+                                .into_iter()
+                                .filter(|plugin| {
+                                    plugin.name != HELP_PLUGIN_NAME
+                                        && plugin
+                                            .is_available_in(event.group_id, Some(event.user_id))
+                                })
+                                .collect();
+
+                            let reply = match name {
+                                None => config.render_list(plugins, command),
+                                Some(name) => match plugins.iter().find(|p| p.name == name) {
+                                    Some(plugin) => config.render_detail(plugin),
+                                    None => fill_placeholders(
+                                        &config.replies.not_found,
+                                        &[("plugin", name)],
+                                    ),
+                                },
+                            };
+                            event.reply(reply);
+                        }
+                    });
+                })
+            }),
+        )
+    }
+
+    /// 返回触发的命令，以及要查看详情的插件
+    fn parse<'a>(&self, text: &'a str) -> Option<(&str, Option<&'a str>)> {
+        let text = text.trim();
+        self.commands.iter().find_map(|command| {
+            let rest = text.strip_prefix(command.as_str())?;
+            if rest.is_empty() {
+                Some((command.as_str(), None))
+            } else if rest.starts_with(char::is_whitespace) {
+                Some((command.as_str(), Some(rest.trim())))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn render_list(&self, mut plugins: Vec<PluginInfo>, command: &str) -> String {
+        let r = &self.replies;
+        if plugins.is_empty() {
+            return r.no_plugins.clone();
+        }
+        plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut reply = r.header.clone();
+        for plugin in &plugins {
+            reply.push('\n');
+            match &plugin.meta.description {
+                Some(description) => reply.push_str(&fill_placeholders(
+                    &r.plugin_line,
+                    &[("plugin", &plugin.name), ("description", description)],
+                )),
+                None => reply.push_str(&plugin.name),
+            }
+        }
+        reply.push('\n');
+        reply.push_str(&fill_placeholders(&r.footer, &[("command", command)]));
+        reply
+    }
+
+    fn render_detail(&self, plugin: &PluginInfo) -> String {
+        let r = &self.replies;
+        let PluginMeta {
+            description,
+            author,
+            usage,
+            commands,
+        } = &plugin.meta;
+
+        let mut lines = vec![format!("{} {}", plugin.name, plugin.version)];
+        if let Some(description) = description {
+            lines.push(description.clone());
+        }
+        if let Some(author) = author {
+            lines.push(fill_placeholders(&r.author, &[("author", author)]));
+        }
+        if let Some(usage) = usage {
+            lines.push(fill_placeholders(&r.usage, &[("usage", usage)]));
+        }
+        if !commands.is_empty() {
+            lines.push(r.commands.clone());
+            for command in commands {
+                lines.push(fill_placeholders(
+                    &r.command_line,
+                    &[
+                        ("trigger", &command.trigger),
+                        ("description", &command.description),
+                    ],
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// 帮助插件的回复文本
+///
+/// 文本中的 `{plugin}` 等占位符会被替换。
+#[derive(Clone, Debug)]
+pub struct HelpReplies {
+    /// 插件列表的标题
+    pub header: String,
+    /// 插件列表的每一行，可用 `{plugin}`、`{description}`。没有说明的插件只显示名称
+    pub plugin_line: String,
+    /// 插件列表的结尾，可用 `{command}`
+    pub footer: String,
+    pub no_plugins: String,
+    /// 可用 `{plugin}`
+    pub not_found: String,
+    /// 可用 `{author}`
+    pub author: String,
+    /// 可用 `{usage}`
+    pub usage: String,
+    /// 命令列表的标题
+    pub commands: String,
+    /// 命令列表的每一行，可用 `{trigger}`、`{description}`
+    pub command_line: String,
+}
+
+impl Default for HelpReplies {
+    fn default() -> Self {
+        Self::zh()
+    }
+}
+
+impl HelpReplies {
+    pub fn zh() -> Self {
+        HelpReplies {
+            header: "插件列表：".to_string(),
+            plugin_line: "{plugin} - {description}".to_string(),
+            footer: "发送 {command} <插件> 查看详情".to_string(),
+            no_plugins: "没有可用的插件".to_string(),
+            not_found: "找不到插件 {plugin}".to_string(),
+            author: "作者：{author}".to_string(),
+            usage: "用法：{usage}".to_string(),
+            commands: "命令：".to_string(),
+            command_line: "  {trigger} - {description}".to_string(),
+        }
+    }
+
+    pub fn en() -> Self {
+        HelpReplies {
+            header: "Plugins:".to_string(),
+            plugin_line: "{plugin} - {description}".to_string(),
+            footer: "Send {command} <plugin> for details".to_string(),
+            no_plugins: "No plugins available".to_string(),
+            not_found: "Plugin {plugin} not found".to_string(),
+            author: "Author: {author}".to_string(),
+            usage: "Usage: {usage}".to_string(),
+            commands: "Commands:".to_string(),
+            command_line: "  {trigger} - {description}".to_string(),
+        }
+    }
+}
+
+#[test]
+fn render_help() {
+    let help = HelpPlugin::new();
+    assert_eq!(help.parse("help"), Some(("help", None)));
+    assert_eq!(help.parse(" 帮助 hi "), Some(("帮助", Some("hi"))));
+    assert_eq!(help.parse("helpme"), None);
+
+    let plugin = PluginInfo {
+        name: "hi".to_string(),
+        version: "0.1.0".to_string(),
+        enabled: true,
+        enable_on_startup: true,
+        disabled_in: Default::default(),
+        meta: PluginMeta::new()
+            .description("打招呼")
+            .command("hi", "回复 hi"),
+        #[cfg(feature = "plugin-access-control")]
+        access_control: false,
+        #[cfg(feature = "plugin-access-control")]
+        list_mode: crate::plugin::AccessControlMode::WhiteList,
+        #[cfg(feature = "plugin-access-control")]
+        access_list: Default::default(),
+        stats: Default::default(),
+    };
+    assert_eq!(
+        help.render_list(vec![plugin.clone()], "help"),
+        "插件列表：\nhi - 打招呼\n发送 help <插件> 查看详情"
+    );
+    assert_eq!(
+        help.render_detail(&plugin),
+        "hi 0.1.0\n打招呼\n命令：\n  hi - 回复 hi"
+    );
+}
@@ -0,0 +1,170 @@
+use crate::RT;
+use crate::types::PinFut;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// 插件同时运行的监听处理数量上限
+#[derive(Debug, Clone, Copy)]
+pub struct ConcurrencyLimit {
+    /// 同时运行的处理数量，为 0 时视为 1
+    pub max_concurrent: usize,
+    /// 等待队列的容量，`OverflowPolicy::Queue` 时不生效
+    pub queue_capacity: usize,
+    /// 达到上限后如何处理新的事件
+    pub overflow: OverflowPolicy,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(max_concurrent: usize, queue_capacity: usize, overflow: OverflowPolicy) -> Self {
+        ConcurrencyLimit {
+            max_concurrent,
+            queue_capacity,
+            overflow,
+        }
+    }
+}
+
+/// 达到并发上限后的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// 全部排队，不丢弃事件
+    Queue,
+    /// 队列满时丢弃队列中最早的事件，队列容量为 0 时丢弃新到的事件
+    DropOldest,
+    /// 队列满时丢弃新到的事件
+    DropNewest,
+}
+
+pub(crate) struct Limiter {
+    plugin_name: String,
+    limit: ConcurrencyLimit,
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    running: usize,
+    queue: VecDeque<PinFut>,
+}
+
+impl Limiter {
+    pub(crate) fn new(plugin_name: String, limit: ConcurrencyLimit) -> Self {
+        Limiter {
+            plugin_name,
+            limit,
+            state: Mutex::new(LimiterState::default()),
+        }
+    }
+
+    /// 提交一个处理，未达到上限时直接运行，否则按照 `OverflowPolicy` 排队或丢弃
+    pub(crate) fn submit(self: &Arc<Self>, task: PinFut) {
+        let mut state = self.state.lock();
+
+        if state.running < self.limit.max_concurrent.max(1) {
+            state.running += 1;
+            drop(state);
+            self.clone().run(task);
+            return;
+        }
+
+        let full = state.queue.len() >= self.limit.queue_capacity;
+        match self.limit.overflow {
+            OverflowPolicy::Queue => {}
+            OverflowPolicy::DropOldest if full && state.queue.pop_front().is_some() => {
+                log::warn!(
+                    "Plugin '{}' is busy, the oldest queued event was dropped",
+                    self.plugin_name
+                );
+            }
+            // 队列容量为 0 时没有可以丢弃的旧事件
+            OverflowPolicy::DropOldest | OverflowPolicy::DropNewest if full => {
+                log::warn!(
+                    "Plugin '{}' is busy, the incoming event was dropped",
+                    self.plugin_name
+                );
+                return;
+            }
+            _ => {}
+        }
+        state.queue.push_back(task);
+    }
+
+    fn run(self: Arc<Self>, task: PinFut) {
+        RT.spawn(async move {
+            // 处理结束或 panic 时都会释放位置
+            let _release = Release(self);
+            task.await;
+        });
+    }
+
+    fn release(self: &Arc<Self>) {
+        let next = {
+            let mut state = self.state.lock();
+            let next = state.queue.pop_front();
+            if next.is_none() {
+                state.running -= 1;
+            }
+            next
+        };
+
+        if let Some(task) = next {
+            self.clone().run(task);
+        }
+    }
+}
+
+struct Release(Arc<Limiter>);
+
+impl Drop for Release {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+#[test]
+fn limiter_overflow_policy() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::oneshot;
+
+    for (overflow, queue_capacity, expected) in [
+        (OverflowPolicy::DropOldest, 1, vec![0, 3]),
+        (OverflowPolicy::DropOldest, 0, vec![0]),
+        (OverflowPolicy::DropNewest, 1, vec![0, 1]),
+        (OverflowPolicy::DropNewest, 0, vec![0]),
+        (OverflowPolicy::Queue, 1, vec![0, 1, 2, 3]),
+    ] {
+        let limiter = Arc::new(Limiter::new(
+            "test".to_string(),
+            ConcurrencyLimit::new(1, queue_capacity, overflow),
+        ));
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let finished = Arc::new(AtomicUsize::new(0));
+        let (block_tx, block_rx) = oneshot::channel::<()>();
+        let mut block_rx = Some(block_rx);
+
+        for i in 0..4 {
+            let ran = ran.clone();
+            let finished = finished.clone();
+            let block_rx = block_rx.take();
+            limiter.submit(Box::pin(async move {
+                if let Some(rx) = block_rx {
+                    let _ = rx.await;
+                }
+                ran.lock().push(i);
+                finished.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+
+        block_tx.send(()).expect("receiver alive");
+        RT.block_on(async {
+            while finished.load(Ordering::SeqCst) < expected.len()
+                || limiter.state.lock().running != 0
+            {
+                tokio::task::yield_now().await;
+            }
+        });
+
+        assert_eq!(*ran.lock(), expected);
+    }
+}
@@ -1,21 +1,31 @@
 use crate::RT;
 use crate::bot::BotInformation;
 use crate::bot::Host;
+use crate::bot::filter::MsgFilter;
+use crate::bot::message::{PatternMatch, RegexCapture, SegmentPattern};
+use crate::bot::permission::Permission;
 use crate::bot::plugin_builder::event::Event;
 use crate::bot::{Bot, runtimebot::RuntimeBot};
-use crate::event::InternalEvent;
 use crate::event::MsgSendFromServerEvent;
 use crate::event::{AdminMsgEvent, GroupMsgEvent, PrivateMsgEvent};
-use crate::plugin::{PLUGIN_BUILDER, PLUGIN_NAME};
-use crate::types::{ApiAndOneshot, NoArgsFn, PinFut};
+use crate::event::{InternalEvent, Role, message_sender};
+use crate::plugin::health::{ErrorReporter, PanicLimit};
+use crate::plugin::limiter::Limiter;
+use crate::plugin::sequential::{Sequencer, SequentialKey};
+use crate::plugin::{ConcurrencyLimit, PLUGIN_BUILDER, PLUGIN_NAME, PluginMeta};
+use crate::types::{ApiAndOneshot, NoArgsFn};
 use croner::Cron;
 use croner::errors::CronError;
 use event::{MsgEvent, NoticeEvent, RequestEvent};
 use log::error;
 use parking_lot::RwLock;
+use regex::Regex;
 use std::any::Any;
+use std::fmt::Display;
 use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
 // 兼容旧版本
@@ -49,29 +59,139 @@ pub(crate) struct Listen {
     pub(crate) list: Vec<Arc<ListenInner>>,
     pub(crate) drop: Vec<NoArgsFn>,
 }
-impl Listen {
-    pub(crate) fn clear(&mut self) {
-        self.list.clear();
-        self.drop.clear();
-        self.list.shrink_to_fit();
-        self.drop.shrink_to_fit();
-    }
-}
 type ArcTypeDeFn = Arc<
     dyn Fn(&InternalEvent, &BotInformation, &mpsc::Sender<ApiAndOneshot>) -> Option<Arc<dyn Event>>
         + Send
         + Sync,
 >;
 
+/// 监听处理的返回，处理失败时为错误信息
+pub(crate) type HandlerFut = Pin<Box<dyn Future<Output = Option<String>> + Send>>;
+
 #[derive(Clone)]
 pub(crate) struct ListenInner {
     pub(crate) type_id: std::any::TypeId,
+    /// 事件类型名，不含路径，用于日志
+    pub(crate) type_name: &'static str,
     pub(crate) type_de: ArcTypeDeFn,
-    pub(crate) handler: Arc<dyn Fn(Arc<dyn Event>) -> PinFut + Send + Sync>,
+    pub(crate) handler: Arc<dyn Fn(Arc<dyn Event>, Matched) -> HandlerFut + Send + Sync>,
+    pub(crate) sequencer: Option<Arc<Sequencer>>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) filters: Vec<ListenFilter>,
+    pub(crate) matcher: Option<ListenMatcher>,
+}
+
+/// 分发前的检查，不通过时此监听不会收到事件
+pub(crate) type ListenFilter = Arc<dyn Fn(&dyn Event, &BotInformation) -> bool + Send + Sync>;
+
+/// 分发前的匹配，在所有检查之后运行一次，匹配不到时此监听不会收到事件，匹配结果交给处理函数
+pub(crate) type ListenMatcher = Arc<dyn Fn(&dyn Event) -> Option<Matched> + Send + Sync>;
+
+/// `ListenMatcher` 的匹配结果
+pub(crate) type Matched = Box<dyn Any + Send>;
+
+/// 监听处理函数的返回值
+///
+/// 通过 `PluginBuilder::on_fallible()`、`PluginBuilder::on_msg_fallible()` 等注册的处理函数可以返回 `Result`，
+/// 在处理函数中使用 `?`。`on_regex()` 等匹配消息的方法也接受返回 `Result` 的处理函数。
+/// 返回的 `Err` 会被记录到日志与插件的统计中，如果插件设置了 `PluginBuilder::set_error_reply()`，还会回复给用户。
+///
+/// ```no_run
+/// use kovi::PluginBuilder;
+///
+/// async fn start() {
+///     PluginBuilder::on_msg_fallible(|event| async move {
+///         let text = event.borrow_text().ok_or("没有文字")?;
+///         let num: i64 = text.parse()?;
+///         event.reply(format!("{}", num * 2));
+///         Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+///     });
+/// }
+/// ```
+pub trait HandlerResult: Send {
+    /// 处理失败时返回错误信息
+    fn into_error(self) -> Option<String>;
+}
+
+impl HandlerResult for () {
+    fn into_error(self) -> Option<String> {
+        None
+    }
+}
+
+impl<T: Send, E: Display + Send> HandlerResult for Result<T, E> {
+    fn into_error(self) -> Option<String> {
+        self.err().map(|e| e.to_string())
+    }
+}
+
+/// 监听的选项，通过 `PluginBuilder::on_with()` 使用
+#[derive(Clone, Default)]
+pub struct ListenOptions {
+    pub(crate) sequential: Option<SequentialKey>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) filters: Vec<ListenFilter>,
+}
+
+impl ListenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 分组相同的事件按到达顺序依次处理，不同分组之间仍然并行。
+    ///
+    /// 会覆盖插件通过 `PluginBuilder::set_sequential()` 设置的分组。
+    pub fn sequential(mut self, key: SequentialKey) -> Self {
+        self.sequential = Some(key);
+        self
+    }
+
+    /// 处理的超时时间，超时后取消处理，见 `CancellationToken`。
+    ///
+    /// 会覆盖插件通过 `PluginBuilder::set_timeout()` 设置的超时时间。
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 只处理群身份不低于 `role` 的发送人的消息，例如 `Role::Admin` 时群管理员与群主都可以触发。
+    ///
+    /// 不是消息事件，或者是私聊消息时，此监听不会收到事件。
+    pub fn require_role(mut self, role: Role) -> Self {
+        self.filters.push(Arc::new(move |event, _| {
+            message_sender(event).is_some_and(|sender| sender.role >= Some(role))
+        }));
+        self
+    }
+
+    /// 只处理触发者拥有 `permission` 的事件，见 `Permission`。
+    pub fn permission(mut self, permission: Permission) -> Self {
+        self.filters.push(Arc::new(move |event, bot_info| {
+            permission.check(event, bot_info)
+        }));
+        self
+    }
+
+    /// 只处理满足 `filter` 的事件，见 `MsgFilter`。
+    pub fn filter(mut self, filter: MsgFilter) -> Self {
+        self.filters
+            .push(Arc::new(move |event, _| filter.check(event)));
+        self
+    }
+
+    /// 只处理能被 `pattern` 匹配的消息，见 `SegmentPattern`。
+    ///
+    /// 不是消息事件时，此监听不会收到事件。
+    pub fn pattern(mut self, pattern: SegmentPattern) -> Self {
+        self.filters.push(Arc::new(move |event, _| {
+            pattern.match_event(event).is_some()
+        }));
+        self
+    }
 }
 
 impl Listen {
-    pub(crate) fn on<T, F, Fut>(&mut self, handler: F)
+    pub(crate) fn on<T, F, Fut>(&mut self, options: ListenOptions, handler: F)
     where
         T: Event,
         F: Fn(Arc<T>) -> Fut + Send + Sync + 'static,
@@ -79,25 +199,92 @@ impl Listen {
         Fut::Output: Send,
     {
         let handler = Arc::new(handler);
+        self.push::<T>(
+            options,
+            None,
+            Arc::new(move |event, _| {
+                let handler = handler.clone();
+                Box::pin(async move {
+                    handler(event).await;
+                    None
+                })
+            }),
+        );
+    }
+
+    pub(crate) fn on_fallible<T, F, Fut>(&mut self, options: ListenOptions, handler: F)
+    where
+        T: Event,
+        F: Fn(Arc<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        let handler = Arc::new(handler);
+        self.push::<T>(
+            options,
+            None,
+            Arc::new(move |event, _| {
+                let handler = handler.clone();
+                Box::pin(async move { handler(event).await.into_error() })
+            }),
+        );
+    }
+
+    /// 注册需要匹配的处理函数，`matcher` 只在分发时运行一次，结果交给处理函数
+    pub(crate) fn on_matched<T, M, F, Fut>(
+        &mut self,
+        options: ListenOptions,
+        matcher: impl Fn(&T) -> Option<M> + Send + Sync + 'static,
+        handler: F,
+    ) where
+        T: Event,
+        M: Send + 'static,
+        F: Fn(Arc<T>, M) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        let handler = Arc::new(handler);
+        self.push::<T>(
+            options,
+            Some(Arc::new(move |event| {
+                let event = (event as &dyn Any).downcast_ref::<T>()?;
+                Some(Box::new(matcher(event)?) as Matched)
+            })),
+            Arc::new(move |event, matched| {
+                let Ok(matched) = matched.downcast::<M>() else {
+                    return Box::pin(async { None });
+                };
+                let handler = handler.clone();
+                Box::pin(async move { handler(event, *matched).await.into_error() })
+            }),
+        );
+    }
 
+    fn push<T: Event>(
+        &mut self,
+        options: ListenOptions,
+        matcher: Option<ListenMatcher>,
+        handler: Arc<dyn Fn(Arc<T>, Matched) -> HandlerFut + Send + Sync>,
+    ) {
         self.list.push(Arc::new(ListenInner {
+            sequencer: options.sequential.map(|key| Arc::new(Sequencer::new(key))),
+            timeout: options.timeout,
+            filters: options.filters,
+            matcher,
             type_id: std::any::TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>()
+                .rsplit("::")
+                .next()
+                .unwrap_or_default(),
             type_de: Arc::new(|value, bot_info, sender| {
                 Some(Arc::new(T::de(value, bot_info, sender)?))
             }),
-            handler: Arc::new(move |evt: Arc<dyn Event>| {
-                let downcasted = evt.downcast_arc::<T>();
-
-                match downcasted {
-                    Ok(downcasted) => Box::pin({
-                        let handler = handler.clone();
-                        async move {
-                            handler(downcasted).await;
-                        }
-                    }),
-                    Err(_) => Box::pin(async {}),
-                }
-            }),
+            handler: Arc::new(
+                move |evt: Arc<dyn Event>, matched| match evt.downcast_arc::<T>() {
+                    Ok(downcasted) => handler(downcasted, matched),
+                    Err(_) => Box::pin(async { None }),
+                },
+            ),
         }));
     }
 }
@@ -150,12 +337,160 @@ impl PluginBuilder {
     where
         Fut: Future + Send,
         Fut::Output: Send,
+    {
+        Self::on_with::<T, _>(ListenOptions::default(), handler)
+    }
+
+    /// 注册事件处理函数，并为此监听设置选项。
+    ///
+    /// ```no_run
+    /// use kovi::plugin::{ListenOptions, SequentialKey};
+    /// use kovi::{MsgEvent, PluginBuilder};
+    /// use std::sync::Arc;
+    ///
+    /// async fn start() {
+    ///     PluginBuilder::on_with(
+    ///         ListenOptions::new().sequential(SequentialKey::chat()),
+    ///         |event: Arc<MsgEvent>| async move {
+    ///             // 同一会话的消息会按顺序依次处理
+    ///         },
+    ///     );
+    /// }
+    /// ```
+    pub fn on_with<T: Event, Fut>(
+        options: ListenOptions,
+        handler: impl Fn(Arc<T>) -> Fut + Send + Sync + 'static,
+    ) where
+        Fut: Future + Send,
+        Fut::Output: Send,
+    {
+        assert_right_place!(PLUGIN_BUILDER.try_with(|p| {
+            let mut bot = p.bot.write();
+            let bot_plugin = bot.plugins.get_mut(&p.runtime_bot.plugin_name).expect("");
+
+            Arc::make_mut(&mut bot_plugin.listen).on(options, handler);
+            bot.mark_snapshot_dirty();
+        }));
+    }
+
+    /// 注册会返回错误的事件处理函数，见 `HandlerResult`。
+    pub fn on_fallible<T: Event, Fut>(handler: impl Fn(Arc<T>) -> Fut + Send + Sync + 'static)
+    where
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        Self::on_with_fallible::<T, _>(ListenOptions::default(), handler)
+    }
+
+    /// 注册会返回错误的事件处理函数，并为此监听设置选项，见 `HandlerResult`。
+    pub fn on_with_fallible<T: Event, Fut>(
+        options: ListenOptions,
+        handler: impl Fn(Arc<T>) -> Fut + Send + Sync + 'static,
+    ) where
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
     {
         assert_right_place!(PLUGIN_BUILDER.try_with(|p| {
             let mut bot = p.bot.write();
             let bot_plugin = bot.plugins.get_mut(&p.runtime_bot.plugin_name).expect("");
 
-            bot_plugin.listen.on(handler);
+            Arc::make_mut(&mut bot_plugin.listen).on_fallible(options, handler);
+            bot.mark_snapshot_dirty();
+        }));
+    }
+
+    /// 此插件所有监听中分组相同的事件，按到达顺序依次处理，不同分组之间仍然并行。
+    ///
+    /// 通过 `ListenOptions::sequential()` 单独设置了分组的监听不受影响。
+    pub fn set_sequential(key: SequentialKey) {
+        assert_right_place!(PLUGIN_BUILDER.try_with(|p| {
+            let mut bot = p.bot.write();
+            let bot_plugin = bot
+                .plugins
+                .get_mut(&p.runtime_bot.plugin_name)
+                .expect("unreachable");
+
+            bot_plugin.sequencer = Some(Arc::new(Sequencer::new(key)));
+            bot.mark_snapshot_dirty();
+        }));
+    }
+
+    /// 设置插件的说明，见 `PluginMeta`。
+    pub fn set_meta(meta: PluginMeta) {
+        assert_right_place!(PLUGIN_BUILDER.try_with(|p| {
+            let mut bot = p.bot.write();
+            let bot_plugin = bot
+                .plugins
+                .get_mut(&p.runtime_bot.plugin_name)
+                .expect("unreachable");
+
+            bot_plugin.meta = Arc::new(meta);
+            bot.mark_snapshot_dirty();
+        }));
+    }
+
+    /// 限制此插件同时运行的监听处理数量。
+    ///
+    /// 达到上限后，新的事件按照 `limit.overflow` 排队或丢弃，丢弃时会输出警告。
+    pub fn set_concurrency_limit(limit: ConcurrencyLimit) {
+        assert_right_place!(PLUGIN_BUILDER.try_with(|p| {
+            let mut bot = p.bot.write();
+            let bot_plugin = bot
+                .plugins
+                .get_mut(&p.runtime_bot.plugin_name)
+                .expect("unreachable");
+
+            bot_plugin.limiter = Some(Arc::new(Limiter::new(
+                p.runtime_bot.plugin_name.clone(),
+                limit,
+            )));
+            bot.mark_snapshot_dirty();
+        }));
+    }
+
+    /// 设置插件在 `limit.window` 时间内 panic 达到 `limit.max_panics` 次时自动关闭。
+    ///
+    /// 默认不会自动关闭。
+    pub fn set_panic_limit(limit: PanicLimit) {
+        assert_right_place!(PLUGIN_BUILDER.try_with(|p| {
+            let bot = p.bot.read();
+            let bot_plugin = bot
+                .plugins
+                .get(&p.runtime_bot.plugin_name)
+                .expect("unreachable");
+
+            bot_plugin.health.set_panic_limit(Some(limit));
+        }));
+    }
+
+    /// 此插件所有监听处理的超时时间，超时后取消处理，见 `CancellationToken`。
+    ///
+    /// 通过 `ListenOptions::timeout()` 单独设置了超时时间的监听不受影响。默认不超时。
+    pub fn set_timeout(timeout: Duration) {
+        assert_right_place!(PLUGIN_BUILDER.try_with(|p| {
+            let mut bot = p.bot.write();
+            let bot_plugin = bot
+                .plugins
+                .get_mut(&p.runtime_bot.plugin_name)
+                .expect("unreachable");
+
+            bot_plugin.timeout = Some(timeout);
+            bot.mark_snapshot_dirty();
+        }));
+    }
+
+    /// 处理函数返回 `Err` 时回复给用户的消息，只回复消息事件。
+    ///
+    /// 消息中的 `{error}` 会被替换为错误信息。默认不回复。
+    pub fn set_error_reply<S: Into<String>>(reply: S) {
+        assert_right_place!(PLUGIN_BUILDER.try_with(|p| {
+            let bot = p.bot.read();
+            let bot_plugin = bot
+                .plugins
+                .get(&p.runtime_bot.plugin_name)
+                .expect("unreachable");
+
+            bot_plugin.health.set_error_reply(Some(reply.into()));
         }));
     }
 
@@ -169,6 +504,151 @@ impl PluginBuilder {
         PluginBuilder::on::<MsgEvent, _>(handler)
     }
 
+    /// 注册会返回错误的事件处理函数，见 `HandlerResult`。
+    pub fn on_msg_fallible<F, Fut>(handler: F)
+    where
+        F: Fn(Arc<MsgEvent>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        PluginBuilder::on_fallible::<MsgEvent, _>(handler)
+    }
+
+    /// 注册满足 `filter` 的消息的处理函数，不满足的消息不会产生任务。
+    ///
+    /// ```no_run
+    /// use kovi::PluginBuilder;
+    /// use kovi::plugin::MsgFilter;
+    ///
+    /// async fn start() {
+    ///     PluginBuilder::on_msg_filtered(
+    ///         MsgFilter::InGroup(123456).and(MsgFilter::starts_with("/ping")),
+    ///         |event| async move {
+    ///             event.reply("pong");
+    ///         },
+    ///     );
+    /// }
+    /// ```
+    pub fn on_msg_filtered<F, Fut>(filter: MsgFilter, handler: F)
+    where
+        F: Fn(Arc<MsgEvent>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        PluginBuilder::on_with_fallible::<MsgEvent, _>(ListenOptions::new().filter(filter), handler)
+    }
+
+    /// 注册能被 `pattern` 匹配的消息的处理函数，处理函数会收到匹配到的内容。
+    ///
+    /// 不匹配的消息不会产生任务。
+    ///
+    /// ```no_run
+    /// use kovi::PluginBuilder;
+    /// use kovi::bot::message::SegmentPattern;
+    ///
+    /// async fn start() {
+    ///     let pattern = SegmentPattern::new().reply().text("quote");
+    ///     PluginBuilder::on_pattern(pattern, |event, captures| async move {
+    ///         let id = captures.reply_id();
+    ///     });
+    /// }
+    /// ```
+    pub fn on_pattern<F, Fut>(pattern: SegmentPattern, handler: F)
+    where
+        F: Fn(Arc<MsgEvent>, PatternMatch) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        Self::on_matched::<MsgEvent, _, _, _>(move |event| pattern.match_event(event), handler)
+    }
+
+    /// 注册文字能被正则 `pattern` 匹配的消息的处理函数，处理函数会收到匹配到的分组。
+    ///
+    /// 正则只在注册时编译一次，不匹配的消息不会产生任务。
+    ///
+    /// ```no_run
+    /// use kovi::PluginBuilder;
+    ///
+    /// async fn start() -> Result<(), kovi::regex::Error> {
+    ///     PluginBuilder::on_regex(r"^echo (?<text>.+)$", |event, captures| async move {
+    ///         if let Some(text) = captures.name("text") {
+    ///             event.reply(text);
+    ///         }
+    ///     })?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn on_regex<F, Fut>(pattern: &str, handler: F) -> Result<(), regex::Error>
+    where
+        F: Fn(Arc<MsgEvent>, RegexCapture) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        Self::on_regex_with(pattern, MsgEvent::borrow_text, handler)
+    }
+
+    /// 注册群消息的正则处理函数，见 `PluginBuilder::on_regex()`。
+    pub fn on_group_regex<F, Fut>(pattern: &str, handler: F) -> Result<(), regex::Error>
+    where
+        F: Fn(Arc<GroupMsgEvent>, RegexCapture) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        Self::on_regex_with(pattern, GroupMsgEvent::borrow_text, handler)
+    }
+
+    /// 注册私聊消息的正则处理函数，见 `PluginBuilder::on_regex()`。
+    pub fn on_private_regex<F, Fut>(pattern: &str, handler: F) -> Result<(), regex::Error>
+    where
+        F: Fn(Arc<PrivateMsgEvent>, RegexCapture) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        Self::on_regex_with(pattern, PrivateMsgEvent::borrow_text, handler)
+    }
+
+    fn on_regex_with<T, F, Fut>(
+        pattern: &str,
+        text: fn(&T) -> Option<&str>,
+        handler: F,
+    ) -> Result<(), regex::Error>
+    where
+        T: Event,
+        F: Fn(Arc<T>, RegexCapture) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        let regex = Regex::new(pattern)?;
+        Self::on_matched::<T, _, _, _>(
+            move |event| RegexCapture::new(&regex, text(event)?),
+            handler,
+        );
+        Ok(())
+    }
+
+    fn on_matched<T, M, F, Fut>(
+        matcher: impl Fn(&T) -> Option<M> + Send + Sync + 'static,
+        handler: F,
+    ) where
+        T: Event,
+        M: Send + 'static,
+        F: Fn(Arc<T>, M) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        assert_right_place!(PLUGIN_BUILDER.try_with(|p| {
+            let mut bot = p.bot.write();
+            let bot_plugin = bot.plugins.get_mut(&p.runtime_bot.plugin_name).expect("");
+
+            Arc::make_mut(&mut bot_plugin.listen).on_matched(
+                ListenOptions::new(),
+                matcher,
+                handler,
+            );
+            bot.mark_snapshot_dirty();
+        }));
+    }
+
     /// 注册事件处理函数。
     pub fn on_admin_msg<F, Fut>(handler: F)
     where
@@ -179,6 +659,16 @@ impl PluginBuilder {
         PluginBuilder::on::<AdminMsgEvent, _>(handler)
     }
 
+    /// 注册会返回错误的事件处理函数，见 `HandlerResult`。
+    pub fn on_admin_msg_fallible<F, Fut>(handler: F)
+    where
+        F: Fn(Arc<AdminMsgEvent>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        PluginBuilder::on_fallible::<AdminMsgEvent, _>(handler)
+    }
+
     /// 注册事件处理函数。
     pub fn on_private_msg<F, Fut>(handler: F)
     where
@@ -189,6 +679,16 @@ impl PluginBuilder {
         PluginBuilder::on::<PrivateMsgEvent, _>(handler)
     }
 
+    /// 注册会返回错误的事件处理函数，见 `HandlerResult`。
+    pub fn on_private_msg_fallible<F, Fut>(handler: F)
+    where
+        F: Fn(Arc<PrivateMsgEvent>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        PluginBuilder::on_fallible::<PrivateMsgEvent, _>(handler)
+    }
+
     /// 注册事件处理函数。
     pub fn on_group_msg<F, Fut>(handler: F)
     where
@@ -199,6 +699,16 @@ impl PluginBuilder {
         PluginBuilder::on::<GroupMsgEvent, _>(handler)
     }
 
+    /// 注册会返回错误的事件处理函数，见 `HandlerResult`。
+    pub fn on_group_msg_fallible<F, Fut>(handler: F)
+    where
+        F: Fn(Arc<GroupMsgEvent>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: HandlerResult,
+    {
+        PluginBuilder::on_fallible::<GroupMsgEvent, _>(handler)
+    }
+
     #[deprecated(
         note = "请使用 `PluginBuilder::on::(|event: Arc<MsgSendFromServerEvent>| fn())` 代替"
     )]
@@ -270,7 +780,7 @@ impl PluginBuilder {
                 .get_mut(&p.runtime_bot.plugin_name)
                 .expect("unreachable");
 
-            bot_plugin.listen.drop.push(Arc::new({
+            Arc::make_mut(&mut bot_plugin.listen).drop.push(Arc::new({
                 let handler = Arc::new(handler);
                 move || {
                     Box::pin({
@@ -324,10 +834,18 @@ impl PluginBuilder {
         Fut::Output: Send,
     {
         let name = Arc::new(p.runtime_bot.plugin_name.clone());
-        let mut enabled = {
+        let (mut enabled, reporter) = {
             let bot = p.bot.read();
             let plugin = bot.plugins.get(&*name).expect("unreachable");
-            plugin.enabled.subscribe()
+            let reporter = ErrorReporter {
+                plugin_name: name.clone(),
+                health: plugin.health.clone(),
+                event_tx: bot.event_tx.clone(),
+                dispatch: true,
+                api_tx: None,
+                enabled: plugin.enabled.clone(),
+            };
+            (plugin.enabled.subscribe(), reporter)
         };
         RT.spawn(PLUGIN_NAME.scope(name.clone(), async move {
 
@@ -345,7 +863,7 @@ impl PluginBuilder {
                             let time = next - now;
                             let duration = std::time::Duration::from_millis(time.num_milliseconds() as u64);
                             tokio::time::sleep(duration).await;
-                            handler().await;
+                            reporter.catch_panic(async { handler().await; }).await;
                         }
                 } => {}
                 _ = async {
@@ -396,3 +914,31 @@ macro_rules! async_move {
         }
     };
 }
+
+#[test]
+fn listeners_accept_any_output_and_fallible_ones_report_errors() {
+    struct Ping;
+    impl Event for Ping {
+        fn de(
+            _: &InternalEvent,
+            _: &BotInformation,
+            _: &mpsc::Sender<ApiAndOneshot>,
+        ) -> Option<Self> {
+            Some(Ping)
+        }
+    }
+
+    let mut listen = Listen::default();
+    listen.on(ListenOptions::new(), |_: Arc<Ping>| async { 42 });
+    listen.on_fallible(ListenOptions::new(), |_: Arc<Ping>| async {
+        "x".parse::<i64>()
+    });
+
+    let results: Vec<Option<String>> = listen
+        .list
+        .iter()
+        .map(|inner| RT.block_on((inner.handler)(Arc::new(Ping), Box::new(()))))
+        .collect();
+    assert_eq!(results[0], None);
+    assert!(results[1].is_some());
+}
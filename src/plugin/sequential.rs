@@ -0,0 +1,198 @@
+use crate::bot::plugin_builder::event::Event;
+use ahash::HashMap;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::oneshot;
+
+type KeyFn = Arc<dyn Fn(&dyn Event) -> Option<u64> + Send + Sync>;
+
+/// 顺序处理的分组依据
+///
+/// 分组相同的事件严格按照到达顺序依次处理，不同分组之间仍然并行。
+/// 取不到分组的事件不参与排队。
+#[derive(Clone)]
+pub struct SequentialKey(KeyFn);
+
+impl SequentialKey {
+    /// 按群号分组，不属于群的事件不参与排队
+    pub fn group() -> Self {
+        SequentialKey(Arc::new(|event| Some(hash_key(event.event_group_id()?))))
+    }
+
+    /// 按触发事件的用户分组
+    pub fn user() -> Self {
+        SequentialKey(Arc::new(|event| Some(hash_key(event.event_user_id()?))))
+    }
+
+    /// 按会话分组，群内按群号，私聊按用户
+    pub fn chat() -> Self {
+        SequentialKey(Arc::new(|event| match event.event_group_id() {
+            Some(group_id) => Some(hash_key(("group", group_id))),
+            None => Some(hash_key(("user", event.event_user_id()?))),
+        }))
+    }
+
+    /// 自定义分组，返回 `None` 的事件不参与排队
+    ///
+    /// 只对 `T` 类型的事件生效，其他类型的事件不参与排队。
+    ///
+    /// ```no_run
+    /// use kovi::MsgEvent;
+    /// use kovi::plugin::SequentialKey;
+    ///
+    /// fn by_text() -> SequentialKey {
+    ///     SequentialKey::custom(|event: &MsgEvent| event.borrow_text().map(String::from))
+    /// }
+    /// ```
+    pub fn custom<T, K, F>(key: F) -> Self
+    where
+        T: Event,
+        K: Hash,
+        F: Fn(&T) -> Option<K> + Send + Sync + 'static,
+    {
+        SequentialKey(Arc::new(move |event| {
+            let event = (event as &dyn Any).downcast_ref::<T>()?;
+            Some(hash_key(key(event)?))
+        }))
+    }
+}
+
+fn hash_key<K: Hash>(key: K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 为同一分组内的处理排队
+///
+/// 每个处理在开始前等待同分组的上一个处理结束（完成、panic 或被取消都算结束）。
+pub(crate) struct Sequencer {
+    key: SequentialKey,
+    next_id: AtomicU64,
+    tails: Mutex<HashMap<u64, (u64, oneshot::Receiver<()>)>>,
+}
+
+impl Sequencer {
+    pub(crate) fn new(key: SequentialKey) -> Self {
+        Sequencer {
+            key,
+            next_id: AtomicU64::new(0),
+            tails: Mutex::new(HashMap::default()),
+        }
+    }
+
+    /// 在同分组的队尾占一个位置，取不到分组时返回 `None`
+    ///
+    /// 必须按照事件到达的顺序调用。
+    pub(crate) fn order(self: &Arc<Self>, event: &dyn Event) -> Option<Turn> {
+        let key = (self.key.0)(event)?;
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+        let prev = self.tails.lock().insert(key, (id, done_rx));
+
+        Some(Turn {
+            prev: prev.map(|(_, prev)| prev),
+            done: Done {
+                sequencer: self.clone(),
+                key,
+                id,
+                _done_tx: done_tx,
+            },
+        })
+    }
+}
+
+/// 在分组中排到的位置
+pub(crate) struct Turn {
+    prev: Option<oneshot::Receiver<()>>,
+    done: Done,
+}
+
+impl Turn {
+    /// 等同分组的上一个处理结束，返回的 `Done` 释放时通知下一个处理
+    pub(crate) async fn wait(self) -> Done {
+        if let Some(prev) = self.prev {
+            let _ = prev.await;
+        }
+        self.done
+    }
+}
+
+/// 结束时通知下一个处理，如果自己仍是队尾，移除此分组
+pub(crate) struct Done {
+    sequencer: Arc<Sequencer>,
+    key: u64,
+    id: u64,
+    _done_tx: oneshot::Sender<()>,
+}
+
+impl Drop for Done {
+    fn drop(&mut self) {
+        let mut tails = self.sequencer.tails.lock();
+        if tails.get(&self.key).is_some_and(|(id, _)| *id == self.id) {
+            tails.remove(&self.key);
+        }
+    }
+}
+
+#[test]
+fn sequencer_keeps_order_per_key() {
+    use crate::RT;
+    use crate::bot::BotInformation;
+    use crate::event::InternalEvent;
+    use crate::types::ApiAndOneshot;
+    use std::time::Duration;
+
+    struct Chat(i64);
+    impl Event for Chat {
+        fn de(
+            _: &InternalEvent,
+            _: &BotInformation,
+            _: &tokio::sync::mpsc::Sender<ApiAndOneshot>,
+        ) -> Option<Self> {
+            None
+        }
+
+        fn event_group_id(&self) -> Option<i64> {
+            Some(self.0)
+        }
+    }
+
+    let sequencer = Arc::new(Sequencer::new(SequentialKey::group()));
+    let ran = Arc::new(Mutex::new(Vec::new()));
+
+    RT.block_on(async {
+        let mut joins = Vec::new();
+        for i in 0..4u64 {
+            let ran = ran.clone();
+            // 越早的事件处理越慢，没有排队时会倒序完成
+            let task = Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(40 - i * 10)).await;
+                ran.lock().push((i % 2, i));
+            });
+            let turn = sequencer.order(&Chat((i % 2) as i64)).expect("grouped");
+            joins.push(tokio::spawn(async move {
+                let _done = turn.wait().await;
+                task.await;
+            }));
+        }
+        for join in joins {
+            join.await.expect("task panicked");
+        }
+    });
+
+    let ran = ran.lock();
+    for group in 0..2 {
+        let order: Vec<_> = ran
+            .iter()
+            .filter(|(g, _)| *g == group)
+            .map(|(_, i)| *i)
+            .collect();
+        assert!(order.is_sorted());
+    }
+    assert!(sequencer.tails.lock().is_empty());
+}